@@ -0,0 +1,74 @@
+// Compares the `Rc`/`Weak` model (held in a `Registry`) with the generational
+// `Arena` backend on the same workload, through the shared `Backend` trait.
+//
+//     cargo bench --bench backends
+
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+use rc_test2::arena::Arena;
+use rc_test2::{Backend, Registry};
+
+const OWNERS: usize = 1_000;
+const GADGETS_PER_OWNER: i32 = 100;
+
+struct Timings {
+    insert: Duration,
+    lookup: Duration,
+    iterate: Duration,
+    remove: Duration,
+}
+
+fn run<B: Backend>(mut backend: B) -> Timings {
+    let start = Instant::now();
+    let mut owners = Vec::with_capacity(OWNERS);
+    let mut gadgets = Vec::with_capacity(OWNERS * GADGETS_PER_OWNER as usize);
+    for i in 0..OWNERS {
+        let owner = backend.create_owner(&format!("owner {i}"));
+        for id in 0..GADGETS_PER_OWNER {
+            gadgets.push(backend.try_add_gadget(owner, id).unwrap());
+        }
+        owners.push(owner);
+    }
+    let insert = start.elapsed();
+
+    let start = Instant::now();
+    for &owner in &owners {
+        for id in 0..GADGETS_PER_OWNER {
+            black_box(backend.find_gadget(owner, id));
+        }
+    }
+    let lookup = start.elapsed();
+
+    let start = Instant::now();
+    for &owner in &owners {
+        black_box(backend.gadget_ids(owner));
+    }
+    let iterate = start.elapsed();
+
+    let start = Instant::now();
+    for gadget in gadgets.into_iter().step_by(2) {
+        backend.remove_gadget(gadget);
+    }
+    let remove = start.elapsed();
+
+    Timings {
+        insert,
+        lookup,
+        iterate,
+        remove,
+    }
+}
+
+fn report(name: &str, timings: &Timings) {
+    println!(
+        "{name:<8} insert {:>10.2?}  lookup {:>10.2?}  iterate {:>10.2?}  remove half {:>10.2?}",
+        timings.insert, timings.lookup, timings.iterate, timings.remove
+    );
+}
+
+fn main() {
+    println!("{OWNERS} owners x {GADGETS_PER_OWNER} gadgets",);
+    report("Rc/Weak", &run(Registry::new()));
+    report("arena", &run(Arena::new()));
+}
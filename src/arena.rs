@@ -0,0 +1,360 @@
+// An alternative storage backend for the Owner–Gadget model.
+//
+// `Rc<Owner>` plus `Vec<Weak<Gadget>>` costs one heap allocation and
+// reference-count traffic per gadget. Here owners and gadgets live in
+// generational arenas instead: a relationship is an index into a `Vec`
+// plus a generation number. Removing an item bumps the generation of its slot,
+// so a handle to a removed item is detected instead of silently reaching
+// whatever reuses the slot, much like a `Weak` that fails to upgrade.
+
+use std::collections::BTreeMap;
+
+use crate::error::Error;
+use crate::owner::fail;
+
+/// Refers to an owner stored in an [`Arena`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct OwnerKey {
+    index: u32,
+    generation: u32,
+}
+
+/// Refers to a gadget stored in an [`Arena`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct GadgetKey {
+    index: u32,
+    generation: u32,
+}
+
+struct Slot<T> {
+    generation: u32,
+    value: Option<T>,
+}
+
+// A `Vec` of slots with a free list; freed slots are reused by later inserts.
+struct Slots<T> {
+    slots: Vec<Slot<T>>,
+    free: Vec<u32>,
+}
+
+impl<T> Slots<T> {
+    fn new() -> Self {
+        Slots {
+            slots: vec![],
+            free: vec![],
+        }
+    }
+
+    fn insert(&mut self, value: T) -> (u32, u32) {
+        match self.free.pop() {
+            Some(index) => {
+                let slot = &mut self.slots[index as usize];
+                slot.value = Some(value);
+                (index, slot.generation)
+            }
+            None => {
+                let index = u32::try_from(self.slots.len()).expect("arena is full");
+                self.slots.push(Slot {
+                    generation: 0,
+                    value: Some(value),
+                });
+                (index, 0)
+            }
+        }
+    }
+
+    fn get(&self, index: u32, generation: u32) -> Option<&T> {
+        let slot = self.slots.get(index as usize)?;
+        if slot.generation != generation {
+            return None;
+        }
+        slot.value.as_ref()
+    }
+
+    fn get_mut(&mut self, index: u32, generation: u32) -> Option<&mut T> {
+        let slot = self.slots.get_mut(index as usize)?;
+        if slot.generation != generation {
+            return None;
+        }
+        slot.value.as_mut()
+    }
+
+    fn remove(&mut self, index: u32, generation: u32) -> Option<T> {
+        let slot = self.slots.get_mut(index as usize)?;
+        if slot.generation != generation {
+            return None;
+        }
+        let value = slot.value.take()?;
+        slot.generation = slot.generation.wrapping_add(1);
+        self.free.push(index);
+        Some(value)
+    }
+
+    fn len(&self) -> usize {
+        self.slots.len() - self.free.len()
+    }
+}
+
+struct OwnerNode<O> {
+    name: String,
+    data: O,
+    // Plays the role of `Owner::gadgets`, indexed by `Gadget::id`.
+    gadgets: BTreeMap<i32, GadgetKey>,
+}
+
+struct GadgetNode<G> {
+    id: i32,
+    data: G,
+    // Plays the role of the `Rc<Owner>` back-reference.
+    owner: OwnerKey,
+}
+
+/// Owners and gadgets stored in generational arenas.
+///
+/// Gadgets are removed explicitly rather than dropped,
+/// so an owner's gadget list never holds dangling entries.
+pub struct Arena<O = (), G = ()> {
+    owners: Slots<OwnerNode<O>>,
+    gadgets: Slots<GadgetNode<G>>,
+}
+
+impl<O, G> Default for Arena<O, G> {
+    fn default() -> Self {
+        Arena {
+            owners: Slots::new(),
+            gadgets: Slots::new(),
+        }
+    }
+}
+
+impl Arena {
+    pub fn new() -> Self {
+        Arena::default()
+    }
+
+    /// Creates an owner without any gadgets.
+    pub fn create_owner(&mut self, name: impl Into<String>) -> OwnerKey {
+        self.create_owner_with(name, ())
+    }
+}
+
+impl<O, G> Arena<O, G> {
+    /// Creates an owner carrying `data` as its payload.
+    pub fn create_owner_with(&mut self, name: impl Into<String>, data: O) -> OwnerKey {
+        let (index, generation) = self.owners.insert(OwnerNode {
+            name: name.into(),
+            data,
+            gadgets: BTreeMap::new(),
+        });
+        OwnerKey { index, generation }
+    }
+
+    fn owner_node(&self, owner: OwnerKey) -> Option<&OwnerNode<O>> {
+        self.owners.get(owner.index, owner.generation)
+    }
+
+    fn gadget_node(&self, gadget: GadgetKey) -> Option<&GadgetNode<G>> {
+        self.gadgets.get(gadget.index, gadget.generation)
+    }
+
+    pub fn owner_name(&self, owner: OwnerKey) -> Option<&str> {
+        Some(&self.owner_node(owner)?.name)
+    }
+
+    pub fn owner_data(&self, owner: OwnerKey) -> Option<&O> {
+        Some(&self.owner_node(owner)?.data)
+    }
+
+    /// Renames an owner, returning `false` if the key is stale.
+    pub fn rename_owner(&mut self, owner: OwnerKey, name: impl Into<String>) -> bool {
+        match self.owners.get_mut(owner.index, owner.generation) {
+            Some(node) => {
+                node.name = name.into();
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Removes an owner and returns its payload.
+    ///
+    /// Fails with [`Error::UnknownOwner`] if the key is stale,
+    /// and with [`Error::OwnerInUse`] while it still has gadgets.
+    pub fn try_remove_owner(&mut self, owner: OwnerKey) -> Result<O, Error> {
+        let node = self.owner_node(owner).ok_or(Error::UnknownOwner)?;
+        if !node.gadgets.is_empty() {
+            return Err(Error::OwnerInUse);
+        }
+        let node = self.owners.remove(owner.index, owner.generation).unwrap();
+        Ok(node.data)
+    }
+
+    /// Like [`Arena::try_remove_owner`], but panics on failure.
+    pub fn remove_owner(&mut self, owner: OwnerKey) -> O {
+        self.try_remove_owner(owner).unwrap_or_else(fail)
+    }
+
+    /// Creates a gadget carrying `data` and registers it on `owner` in one step.
+    ///
+    /// Fails with [`Error::UnknownOwner`] if the owner key is stale,
+    /// and with [`Error::DuplicateId`] if the owner already has a gadget with this id.
+    pub fn try_add_gadget_with(
+        &mut self,
+        owner: OwnerKey,
+        id: i32,
+        data: G,
+    ) -> Result<GadgetKey, Error> {
+        let node = self.owner_node(owner).ok_or(Error::UnknownOwner)?;
+        if node.gadgets.contains_key(&id) {
+            return Err(Error::DuplicateId(id));
+        }
+
+        let (index, generation) = self.gadgets.insert(GadgetNode { id, data, owner });
+        let gadget = GadgetKey { index, generation };
+        self.owners
+            .get_mut(owner.index, owner.generation)
+            .unwrap()
+            .gadgets
+            .insert(id, gadget);
+        Ok(gadget)
+    }
+
+    /// Like [`Arena::try_add_gadget_with`], but panics on failure.
+    pub fn add_gadget_with(&mut self, owner: OwnerKey, id: i32, data: G) -> GadgetKey {
+        self.try_add_gadget_with(owner, id, data)
+            .unwrap_or_else(fail)
+    }
+
+    /// Looks up a gadget of `owner` by its id.
+    pub fn gadget(&self, owner: OwnerKey, id: i32) -> Option<GadgetKey> {
+        self.owner_node(owner)?.gadgets.get(&id).copied()
+    }
+
+    /// Returns the gadgets of `owner`, ordered by id.
+    pub fn gadgets(&self, owner: OwnerKey) -> Vec<GadgetKey> {
+        self.owner_node(owner)
+            .map(|node| node.gadgets.values().copied().collect())
+            .unwrap_or_default()
+    }
+
+    pub fn gadget_id(&self, gadget: GadgetKey) -> Option<i32> {
+        Some(self.gadget_node(gadget)?.id)
+    }
+
+    pub fn gadget_data(&self, gadget: GadgetKey) -> Option<&G> {
+        Some(&self.gadget_node(gadget)?.data)
+    }
+
+    pub fn gadget_owner(&self, gadget: GadgetKey) -> Option<OwnerKey> {
+        Some(self.gadget_node(gadget)?.owner)
+    }
+
+    /// Removes a gadget and deregisters it from its owner, returning its payload.
+    pub fn remove_gadget(&mut self, gadget: GadgetKey) -> Option<G> {
+        let node = self.gadgets.remove(gadget.index, gadget.generation)?;
+        if let Some(owner) = self.owners.get_mut(node.owner.index, node.owner.generation) {
+            owner.gadgets.remove(&node.id);
+        }
+        Some(node.data)
+    }
+
+    pub fn owner_count(&self) -> usize {
+        self.owners.len()
+    }
+
+    pub fn gadget_count(&self) -> usize {
+        self.gadgets.len()
+    }
+}
+
+impl<O, G: Default> Arena<O, G> {
+    /// Creates a gadget with a default payload, see [`Arena::try_add_gadget_with`].
+    pub fn try_add_gadget(&mut self, owner: OwnerKey, id: i32) -> Result<GadgetKey, Error> {
+        self.try_add_gadget_with(owner, id, G::default())
+    }
+
+    /// Creates a gadget with a default payload, see [`Arena::add_gadget_with`].
+    pub fn add_gadget(&mut self, owner: OwnerKey, id: i32) -> GadgetKey {
+        self.add_gadget_with(owner, id, G::default())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn removed_gadgets_leave_stale_keys() {
+        let mut arena = Arena::new();
+        let ann = arena.create_owner("Ann");
+        let first = arena.add_gadget(ann, 1);
+        let second = arena.add_gadget(ann, 2);
+
+        assert_eq!(arena.remove_gadget(first), Some(()));
+        assert_eq!(arena.remove_gadget(first), None);
+        assert_eq!(arena.gadget_id(first), None);
+        assert_eq!(arena.gadget_owner(first), None);
+        assert_eq!(arena.gadget(ann, 1), None);
+        assert_eq!(arena.gadgets(ann), [second]);
+        assert_eq!(arena.gadget_count(), 1);
+    }
+
+    #[test]
+    fn reused_slots_do_not_answer_to_old_keys() {
+        let mut arena = Arena::<(), &str>::default();
+        let ann = arena.create_owner_with("Ann", ());
+        let old = arena.add_gadget_with(ann, 1, "old");
+        arena.remove_gadget(old);
+
+        let new = arena.add_gadget_with(ann, 1, "new");
+        assert_ne!(old, new);
+        assert_eq!(arena.gadget_data(old), None);
+        assert_eq!(arena.gadget_data(new), Some(&"new"));
+        assert_eq!(arena.gadget(ann, 1), Some(new));
+
+        arena.remove_gadget(new);
+        arena.remove_owner(ann);
+        let bob = arena.create_owner_with("Bob", ());
+        assert_ne!(ann, bob);
+        assert_eq!(arena.owner_name(ann), None);
+        assert!(!arena.rename_owner(ann, "Ann"));
+        assert_eq!(arena.owner_name(bob), Some("Bob"));
+        assert_eq!(arena.owner_count(), 1);
+    }
+
+    #[test]
+    fn duplicate_ids_are_rejected_per_owner() {
+        let mut arena = Arena::new();
+        let ann = arena.create_owner("Ann");
+        let bob = arena.create_owner("Bob");
+        arena.add_gadget(ann, 1);
+
+        assert_eq!(arena.try_add_gadget(ann, 1), Err(Error::DuplicateId(1)));
+        assert!(arena.try_add_gadget(bob, 1).is_ok());
+        assert_eq!(arena.gadget_count(), 2);
+    }
+
+    #[test]
+    fn owners_in_use_cannot_be_removed() {
+        let mut arena = Arena::new();
+        let ann = arena.create_owner("Ann");
+        let gadget = arena.add_gadget(ann, 1);
+
+        assert_eq!(arena.try_remove_owner(ann), Err(Error::OwnerInUse));
+        arena.remove_gadget(gadget);
+        assert_eq!(arena.try_remove_owner(ann), Ok(()));
+        assert_eq!(arena.owner_count(), 0);
+    }
+
+    #[test]
+    fn stale_owner_keys_are_rejected() {
+        let mut arena = Arena::new();
+        let ann = arena.create_owner("Ann");
+        arena.remove_owner(ann);
+
+        assert_eq!(arena.try_remove_owner(ann), Err(Error::UnknownOwner));
+        assert_eq!(arena.try_add_gadget(ann, 1), Err(Error::UnknownOwner));
+        assert!(arena.gadgets(ann).is_empty());
+        assert_eq!(arena.gadget_count(), 0);
+    }
+}
@@ -0,0 +1,144 @@
+// The operations shared by the storage backends:
+// the `Rc`/`Weak` model held in a `Registry`, and the generational `Arena`.
+// Code written against `Backend` can switch between them,
+// which is also how the benchmark compares the two.
+
+use crate::arena::{Arena, GadgetKey, OwnerKey};
+use crate::error::Error;
+use crate::registry::{GadgetHandle, OwnerId, Registry};
+
+/// A store of owners and their gadgets.
+pub trait Backend {
+    /// Refers to an owner of this backend.
+    type Owner: Copy + Eq;
+    /// Refers to a gadget of this backend.
+    type Gadget: Copy + Eq;
+
+    fn create_owner(&mut self, name: &str) -> Self::Owner;
+
+    /// Creates a gadget and registers it on `owner`.
+    fn try_add_gadget(&mut self, owner: Self::Owner, id: i32) -> Result<Self::Gadget, Error>;
+
+    /// Looks up a live gadget of `owner` by its id.
+    fn find_gadget(&self, owner: Self::Owner, id: i32) -> Option<Self::Gadget>;
+
+    /// Returns the ids of the live gadgets of `owner`.
+    fn gadget_ids(&self, owner: Self::Owner) -> Vec<i32>;
+
+    fn gadget_id(&self, gadget: Self::Gadget) -> Option<i32>;
+
+    fn gadget_owner(&self, gadget: Self::Gadget) -> Option<Self::Owner>;
+
+    /// Removes a gadget, returning whether it was present.
+    fn remove_gadget(&mut self, gadget: Self::Gadget) -> bool;
+}
+
+impl<O: Default, G: Default> Backend for Registry<O, G> {
+    type Owner = OwnerId;
+    type Gadget = GadgetHandle;
+
+    fn create_owner(&mut self, name: &str) -> OwnerId {
+        self.create_owner_with(name, O::default())
+    }
+
+    fn try_add_gadget(&mut self, owner: OwnerId, id: i32) -> Result<GadgetHandle, Error> {
+        Registry::try_add_gadget(self, owner, id)
+    }
+
+    fn find_gadget(&self, owner: OwnerId, id: i32) -> Option<GadgetHandle> {
+        Registry::find_gadget(self, owner, id)
+    }
+
+    fn gadget_ids(&self, owner: OwnerId) -> Vec<i32> {
+        self.owner(owner)
+            .map(|owner| owner.live_gadgets().map(|gadget| gadget.id()).collect())
+            .unwrap_or_default()
+    }
+
+    fn gadget_id(&self, gadget: GadgetHandle) -> Option<i32> {
+        Some(self.gadget(gadget)?.id())
+    }
+
+    fn gadget_owner(&self, gadget: GadgetHandle) -> Option<OwnerId> {
+        self.owner_id(&self.gadget(gadget)?.owner())
+    }
+
+    fn remove_gadget(&mut self, gadget: GadgetHandle) -> bool {
+        // Dropping the registry's handle frees the gadget,
+        // which deregisters it from its owners.
+        Registry::remove_gadget(self, gadget).map(drop).is_some()
+    }
+}
+
+impl<O: Default, G: Default> Backend for Arena<O, G> {
+    type Owner = OwnerKey;
+    type Gadget = GadgetKey;
+
+    fn create_owner(&mut self, name: &str) -> OwnerKey {
+        self.create_owner_with(name, O::default())
+    }
+
+    fn try_add_gadget(&mut self, owner: OwnerKey, id: i32) -> Result<GadgetKey, Error> {
+        Arena::try_add_gadget(self, owner, id)
+    }
+
+    fn find_gadget(&self, owner: OwnerKey, id: i32) -> Option<GadgetKey> {
+        self.gadget(owner, id)
+    }
+
+    fn gadget_ids(&self, owner: OwnerKey) -> Vec<i32> {
+        self.gadgets(owner)
+            .into_iter()
+            .filter_map(|gadget| Arena::gadget_id(self, gadget))
+            .collect()
+    }
+
+    fn gadget_id(&self, gadget: GadgetKey) -> Option<i32> {
+        Arena::gadget_id(self, gadget)
+    }
+
+    fn gadget_owner(&self, gadget: GadgetKey) -> Option<OwnerKey> {
+        Arena::gadget_owner(self, gadget)
+    }
+
+    fn remove_gadget(&mut self, gadget: GadgetKey) -> bool {
+        Arena::remove_gadget(self, gadget).is_some()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Runs the same scenario against any backend.
+    fn exercise<B: Backend>(mut backend: B)
+    where
+        B::Owner: std::fmt::Debug,
+        B::Gadget: std::fmt::Debug,
+    {
+        let ann = backend.create_owner("Ann");
+        let bob = backend.create_owner("Bob");
+        let first = backend.try_add_gadget(ann, 1).unwrap();
+        let second = backend.try_add_gadget(ann, 2).unwrap();
+        backend.try_add_gadget(bob, 1).unwrap();
+
+        assert_eq!(backend.try_add_gadget(ann, 1), Err(Error::DuplicateId(1)));
+        assert_eq!(backend.find_gadget(ann, 2), Some(second));
+        assert_eq!(backend.gadget_ids(ann), [1, 2]);
+        assert_eq!(backend.gadget_id(second), Some(2));
+        assert_eq!(backend.gadget_owner(first), Some(ann));
+
+        assert!(backend.remove_gadget(first));
+        assert!(!backend.remove_gadget(first));
+        assert_eq!(backend.find_gadget(ann, 1), None);
+        assert_eq!(backend.gadget_id(first), None);
+        assert_eq!(backend.gadget_ids(ann), [2]);
+        assert_eq!(backend.gadget_ids(bob), [1]);
+    }
+
+    #[test]
+    fn registry_and_arena_behave_alike() {
+        exercise(Registry::<(), ()>::new());
+        exercise(Arena::<(), ()>::new());
+    }
+}
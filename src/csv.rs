@@ -0,0 +1,330 @@
+// Import of gadget assignments kept in spreadsheets as `owner_name,gadget_id` rows.
+//
+// Each row creates a gadget on the named owner, reusing the registry's owner
+// of that name or creating it on first use. A bad row is reported with its
+// line number and skipped; the rest of the file is still imported.
+
+use std::collections::HashMap;
+use std::fmt;
+use std::io::{self, BufRead};
+
+use crate::error::Error;
+use crate::registry::{GadgetHandle, OwnerId, Registry};
+
+/// A row that could not be imported.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RowError {
+    /// 1-based line number in the input.
+    pub line: usize,
+    pub error: Error,
+}
+
+impl fmt::Display for RowError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.error)
+    }
+}
+
+impl std::error::Error for RowError {}
+
+/// The outcome of a CSV import.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct CsvImport {
+    /// The gadgets created, in file order.
+    pub gadgets: Vec<GadgetHandle>,
+    /// The owners created because no owner of that name existed yet.
+    pub created_owners: Vec<OwnerId>,
+    /// The rows that were skipped.
+    pub errors: Vec<RowError>,
+}
+
+fn invalid(message: impl Into<String>) -> Error {
+    Error::InvalidCsv(message.into())
+}
+
+// Splits one line into fields.
+// Fields may be quoted, with `""` standing for a literal quote;
+// unquoted fields are trimmed.
+fn split_fields(line: &str) -> Result<Vec<String>, Error> {
+    let mut fields = vec![];
+    let mut chars = line.chars().peekable();
+    loop {
+        while chars.next_if(|c| *c == ' ' || *c == '\t').is_some() {}
+        let mut field = String::new();
+        if chars.next_if_eq(&'"').is_some() {
+            loop {
+                match chars.next() {
+                    Some('"') if chars.next_if_eq(&'"').is_some() => field.push('"'),
+                    Some('"') => break,
+                    Some(c) => field.push(c),
+                    None => return Err(invalid("unterminated quoted field")),
+                }
+            }
+            while chars.next_if(|c| *c == ' ' || *c == '\t').is_some() {}
+            if !matches!(chars.peek(), Some(',') | None) {
+                return Err(invalid("unexpected character after quoted field"));
+            }
+        } else {
+            while let Some(c) = chars.next_if(|c| *c != ',') {
+                field.push(c);
+            }
+            field.truncate(field.trim_end().len());
+        }
+        fields.push(field);
+        if chars.next().is_none() {
+            return Ok(fields);
+        }
+    }
+}
+
+fn is_header(fields: &[String]) -> bool {
+    fields.len() == 2
+        && fields[0].eq_ignore_ascii_case("owner_name")
+        && fields[1].eq_ignore_ascii_case("gadget_id")
+}
+
+impl<O: Default, G: Default> Registry<O, G> {
+    /// Imports `owner_name,gadget_id` rows into the registry.
+    ///
+    /// An optional `owner_name,gadget_id` header line, a leading byte order mark
+    /// and blank lines are skipped.
+    /// Rows with a missing owner name, an invalid gadget id, the wrong number
+    /// of columns or an id the owner already uses are reported in
+    /// [`CsvImport::errors`] without stopping the import.
+    pub fn import_csv(&mut self, text: &str) -> CsvImport {
+        let mut import = CsvImport::default();
+        let mut importer = Importer::default();
+        for (index, line) in text.lines().enumerate() {
+            importer.row(self, &mut import, index + 1, line);
+        }
+        import
+    }
+
+    /// Like [`Registry::import_csv`], reading the rows from `reader`.
+    ///
+    /// Only I/O errors abort the import.
+    pub fn import_csv_from(&mut self, reader: impl BufRead) -> io::Result<CsvImport> {
+        let mut import = CsvImport::default();
+        let mut importer = Importer::default();
+        for (index, line) in reader.lines().enumerate() {
+            importer.row(self, &mut import, index + 1, &line?);
+        }
+        Ok(import)
+    }
+}
+
+// Owners looked up by name so far; avoids scanning the registry on every row.
+#[derive(Default)]
+struct Importer {
+    owners: HashMap<String, OwnerId>,
+}
+
+impl Importer {
+    fn row<O: Default, G: Default>(
+        &mut self,
+        registry: &mut Registry<O, G>,
+        import: &mut CsvImport,
+        line: usize,
+        text: &str,
+    ) {
+        // Spreadsheet exports often start with a UTF-8 byte order mark.
+        let text = match line {
+            1 => text.strip_prefix('\u{feff}').unwrap_or(text),
+            _ => text,
+        };
+        if text.trim().is_empty() {
+            return;
+        }
+        let result = split_fields(text).and_then(|fields| {
+            if line == 1 && is_header(&fields) {
+                return Ok(None);
+            }
+            self.assign(registry, import, &fields).map(Some)
+        });
+        match result {
+            Ok(Some(gadget)) => import.gadgets.push(gadget),
+            Ok(None) => {}
+            Err(error) => import.errors.push(RowError { line, error }),
+        }
+    }
+
+    fn assign<O: Default, G: Default>(
+        &mut self,
+        registry: &mut Registry<O, G>,
+        import: &mut CsvImport,
+        fields: &[String],
+    ) -> Result<GadgetHandle, Error> {
+        let [name, id] = fields else {
+            return Err(invalid(format!(
+                "expected 2 columns, found {}",
+                fields.len()
+            )));
+        };
+        if name.is_empty() {
+            return Err(invalid("missing owner name"));
+        }
+        let id: i32 = id
+            .parse()
+            .map_err(|_| invalid(format!("invalid gadget id {id:?}")))?;
+
+        let owner = match self.owners.get(name) {
+            Some(owner) => *owner,
+            None => {
+                let owner = match registry.find_owner(name) {
+                    Some(owner) => owner,
+                    None => {
+                        let owner = registry.create_owner_with(name.as_str(), O::default());
+                        import.created_owners.push(owner);
+                        owner
+                    }
+                };
+                self.owners.insert(name.clone(), owner);
+                owner
+            }
+        };
+        registry.try_add_gadget(owner, id)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::rc::Rc;
+
+    use super::*;
+
+    fn row_errors(import: &CsvImport) -> Vec<(usize, Error)> {
+        import
+            .errors
+            .iter()
+            .map(|error| (error.line, error.error.clone()))
+            .collect()
+    }
+
+    fn assignments(registry: &Registry) -> Vec<(String, i32)> {
+        registry
+            .gadgets()
+            .map(|(_, gadget)| (gadget.owner().name(), gadget.id()))
+            .collect()
+    }
+
+    #[test]
+    fn header_is_skipped_with_or_without_byte_order_mark() {
+        for text in [
+            "owner_name,gadget_id\nAnn,1\n",
+            "\u{feff}owner_name,gadget_id\r\nAnn,1\r\n",
+            "Owner_Name , Gadget_ID\nAnn,1",
+            "\u{feff}Ann,1",
+        ] {
+            let mut registry = Registry::new();
+            let import = registry.import_csv(text);
+            assert_eq!(row_errors(&import), [], "{text:?}");
+            assert_eq!(assignments(&registry), [("Ann".to_string(), 1)]);
+        }
+    }
+
+    #[test]
+    fn header_is_only_recognised_on_the_first_line() {
+        let mut registry = Registry::new();
+        let import = registry.import_csv("Ann,1\nowner_name,gadget_id\n");
+        assert_eq!(
+            row_errors(&import),
+            [(
+                2,
+                Error::InvalidCsv("invalid gadget id \"gadget_id\"".to_string())
+            )]
+        );
+    }
+
+    #[test]
+    fn quoted_fields() {
+        let mut registry = Registry::new();
+        let import = registry
+            .import_csv("\"Smith, Ann\",1\n  \"Say \"\"hi\"\"\" , \"2\"\n\"open,3\n\"Bob\"x,4\n");
+        assert_eq!(
+            assignments(&registry),
+            [("Smith, Ann".to_string(), 1), ("Say \"hi\"".to_string(), 2)]
+        );
+        assert_eq!(
+            row_errors(&import),
+            [
+                (
+                    3,
+                    Error::InvalidCsv("unterminated quoted field".to_string())
+                ),
+                (
+                    4,
+                    Error::InvalidCsv("unexpected character after quoted field".to_string())
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn bad_rows_are_reported_and_skipped() {
+        let mut registry = Registry::new();
+        let import = registry.import_csv("Ann,1\n,2\n\"\",3\nAnn,x\nAnn\nAnn,4,5\n\nBob,6\n");
+        assert_eq!(
+            assignments(&registry),
+            [("Ann".to_string(), 1), ("Bob".to_string(), 6)]
+        );
+        assert_eq!(
+            row_errors(&import),
+            [
+                (2, Error::InvalidCsv("missing owner name".to_string())),
+                (3, Error::InvalidCsv("missing owner name".to_string())),
+                (4, Error::InvalidCsv("invalid gadget id \"x\"".to_string())),
+                (
+                    5,
+                    Error::InvalidCsv("expected 2 columns, found 1".to_string())
+                ),
+                (
+                    6,
+                    Error::InvalidCsv("expected 2 columns, found 3".to_string())
+                ),
+            ]
+        );
+        assert_eq!(
+            import.errors[0].to_string(),
+            "line 2: invalid CSV: missing owner name"
+        );
+    }
+
+    #[test]
+    fn duplicate_ids_are_reported_per_owner() {
+        let mut registry = Registry::new();
+        let import = registry.import_csv("Ann,1\nBob,1\nAnn,1\n");
+        assert_eq!(row_errors(&import), [(3, Error::DuplicateId(1))]);
+        assert_eq!(import.gadgets.len(), 2);
+        assert_eq!(registry.gadget_count(), 2);
+    }
+
+    #[test]
+    fn owners_are_reused_by_name() {
+        let mut registry = Registry::new();
+        let ann = registry.create_owner("Ann");
+        let import = registry.import_csv("Ann,1\nBob,2\nAnn,3\nBob,4\n");
+
+        let bob = registry.find_owner("Bob").unwrap();
+        assert_eq!(import.created_owners, [bob]);
+        assert_eq!(registry.owner_count(), 2);
+
+        // Each gadget points back to its owner, and the owner lists it.
+        let ann = registry.owner(ann).unwrap();
+        let ids: Vec<i32> = ann.live_gadgets().map(|gadget| gadget.id()).collect();
+        assert_eq!(ids, [1, 3]);
+        for handle in &import.gadgets {
+            let gadget = registry.gadget(*handle).unwrap();
+            let owner = gadget.owner();
+            assert!(Rc::ptr_eq(&owner.gadget(gadget.id()).unwrap(), &gadget));
+        }
+    }
+
+    #[test]
+    fn rows_can_be_read_from_a_reader() {
+        let mut registry = Registry::new();
+        let text = "\u{feff}owner_name,gadget_id\nAnn,1\nAnn,1\n";
+        let import = registry.import_csv_from(text.as_bytes()).unwrap();
+        assert_eq!(import.gadgets.len(), 1);
+        assert_eq!(row_errors(&import), [(3, Error::DuplicateId(1))]);
+    }
+}
@@ -0,0 +1,260 @@
+// Nothing in the ownership API lets an owner hold its gadgets through `Rc`,
+// or a child owner hold its parent through `Rc`: those would be cycles,
+// and an `Rc` cycle is never freed.
+// Payloads are out of its reach though: an owner payload holding an `Rc`
+// to one of the owner's gadgets leaks both.
+//
+// This module checks for such cycles at runtime.
+// It walks the strong edges reachable from a set of roots
+// and reports the first cycle it finds, with the path that forms it.
+// Walking the graph clones `Rc`s and allocates, so it is meant for
+// tests and debug builds rather than hot paths.
+
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::rc::Rc;
+
+use crate::error::Error;
+use crate::gadget::Gadget;
+use crate::owner::{fail, Owner};
+
+/// A node of the object graph that can keep other nodes alive.
+///
+/// Implemented for [`Owner`] and [`Gadget`]; new node types implement it
+/// to take part in cycle detection.
+pub trait StrongEdges {
+    /// A short description of the node used in cycle reports.
+    fn label(&self) -> String;
+
+    /// The nodes this one holds through strong `Rc` pointers.
+    ///
+    /// Fails with [`Error::AlreadyBorrowed`] if the cell holding them is in use.
+    fn try_strong_edges(&self) -> Result<Vec<Rc<dyn StrongEdges>>, Error>;
+
+    /// Like [`StrongEdges::try_strong_edges`], but panics on failure.
+    fn strong_edges(&self) -> Vec<Rc<dyn StrongEdges>> {
+        self.try_strong_edges().unwrap_or_else(fail)
+    }
+}
+
+/// A payload of an [`Owner`] or a [`Gadget`], as seen by the cycle detector.
+///
+/// Payloads holding `Rc`s to nodes of the graph report them here,
+/// so cycles going through a payload are found as well.
+/// The default reports no edges, which suits plain data:
+/// `impl PayloadEdges for MyPayload {}` is enough.
+pub trait PayloadEdges {
+    /// The nodes this payload holds through strong `Rc` pointers.
+    fn strong_edges(&self) -> Vec<Rc<dyn StrongEdges>> {
+        vec![]
+    }
+}
+
+impl PayloadEdges for () {}
+impl PayloadEdges for bool {}
+impl PayloadEdges for i32 {}
+impl PayloadEdges for i64 {}
+impl PayloadEdges for u32 {}
+impl PayloadEdges for u64 {}
+impl PayloadEdges for usize {}
+impl PayloadEdges for f64 {}
+impl PayloadEdges for String {}
+
+impl<T: StrongEdges + 'static> PayloadEdges for Rc<T> {
+    fn strong_edges(&self) -> Vec<Rc<dyn StrongEdges>> {
+        vec![Rc::clone(self) as Rc<dyn StrongEdges>]
+    }
+}
+
+impl<T: PayloadEdges> PayloadEdges for Option<T> {
+    fn strong_edges(&self) -> Vec<Rc<dyn StrongEdges>> {
+        self.iter().flat_map(PayloadEdges::strong_edges).collect()
+    }
+}
+
+impl<T: PayloadEdges> PayloadEdges for Vec<T> {
+    fn strong_edges(&self) -> Vec<Rc<dyn StrongEdges>> {
+        self.iter().flat_map(PayloadEdges::strong_edges).collect()
+    }
+}
+
+impl<T: PayloadEdges> PayloadEdges for BTreeMap<String, T> {
+    fn strong_edges(&self) -> Vec<Rc<dyn StrongEdges>> {
+        self.values().flat_map(PayloadEdges::strong_edges).collect()
+    }
+}
+
+impl<O: PayloadEdges + 'static, G: PayloadEdges + 'static> StrongEdges for Owner<O, G> {
+    fn label(&self) -> String {
+        format!("Owner({})", self.name())
+    }
+
+    // Gadgets are only referenced through `Weak`, so apart from the payload
+    // children are the only strong edges.
+    fn try_strong_edges(&self) -> Result<Vec<Rc<dyn StrongEdges>>, Error> {
+        let mut edges: Vec<Rc<dyn StrongEdges>> = self
+            .try_children()?
+            .into_iter()
+            .map(|child| child as Rc<dyn StrongEdges>)
+            .collect();
+        edges.extend(self.data().strong_edges());
+        Ok(edges)
+    }
+}
+
+impl<O: PayloadEdges + 'static, G: PayloadEdges + 'static> StrongEdges for Gadget<O, G> {
+    fn label(&self) -> String {
+        format!("Gadget({})", self.id())
+    }
+
+    fn try_strong_edges(&self) -> Result<Vec<Rc<dyn StrongEdges>>, Error> {
+        let mut edges: Vec<Rc<dyn StrongEdges>> = self
+            .owners_cell()
+            .try_borrow()?
+            .iter()
+            .map(|owner| Rc::clone(owner) as Rc<dyn StrongEdges>)
+            .collect();
+        edges.extend(self.data().strong_edges());
+        Ok(edges)
+    }
+}
+
+/// A chain of strong edges leading from a node back to itself.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct StrongCycle {
+    /// Labels of the nodes along the cycle; the first node is repeated at the end.
+    pub path: Vec<String>,
+}
+
+impl fmt::Display for StrongCycle {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "strong Rc cycle: {}", self.path.join(" -> "))
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Visit {
+    InProgress,
+    Done,
+}
+
+/// Walks every strong edge reachable from `roots` and returns the first cycle found.
+///
+/// Fails with [`Error::AlreadyBorrowed`] if the edges of a node cannot be read.
+pub fn try_find_strong_cycle(roots: &[Rc<dyn StrongEdges>]) -> Result<Option<StrongCycle>, Error> {
+    let mut visits = HashMap::new();
+    let mut path = vec![];
+    for root in roots {
+        if let Some(cycle) = visit(root, &mut visits, &mut path)? {
+            return Ok(Some(cycle));
+        }
+    }
+    Ok(None)
+}
+
+/// Like [`try_find_strong_cycle`], but panics on failure.
+pub fn find_strong_cycle(roots: &[Rc<dyn StrongEdges>]) -> Option<StrongCycle> {
+    try_find_strong_cycle(roots).unwrap_or_else(fail)
+}
+
+/// Panics with the offending path if a strong cycle is reachable from `roots`.
+pub fn assert_acyclic(roots: &[Rc<dyn StrongEdges>]) {
+    if let Some(cycle) = find_strong_cycle(roots) {
+        panic!("{cycle}");
+    }
+}
+
+/// Like [`assert_acyclic`], but only walks the graph in debug builds.
+pub fn debug_assert_acyclic(roots: &[Rc<dyn StrongEdges>]) {
+    if cfg!(debug_assertions) {
+        assert_acyclic(roots);
+    }
+}
+
+// Depth-first search; `path` holds the nodes currently `InProgress`,
+// so meeting one of them again closes a cycle.
+fn visit(
+    node: &Rc<dyn StrongEdges>,
+    visits: &mut HashMap<*const (), Visit>,
+    path: &mut Vec<Rc<dyn StrongEdges>>,
+) -> Result<Option<StrongCycle>, Error> {
+    let key = Rc::as_ptr(node) as *const ();
+    match visits.get(&key) {
+        Some(Visit::Done) => return Ok(None),
+        Some(Visit::InProgress) => {
+            let start = path
+                .iter()
+                .position(|entry| Rc::as_ptr(entry) as *const () == key)
+                .expect("in-progress node is on the path");
+            let mut labels: Vec<String> = path[start..].iter().map(|entry| entry.label()).collect();
+            labels.push(node.label());
+            return Ok(Some(StrongCycle { path: labels }));
+        }
+        None => {}
+    }
+
+    visits.insert(key, Visit::InProgress);
+    path.push(Rc::clone(node));
+    for next in node.try_strong_edges()? {
+        if let Some(cycle) = visit(&next, visits, path)? {
+            return Ok(Some(cycle));
+        }
+    }
+    path.pop();
+    visits.insert(key, Visit::Done);
+    Ok(None)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::RefCell;
+    use std::panic::{self, AssertUnwindSafe};
+
+    use super::*;
+
+    // An owner payload that can hold one of the owner's gadgets through `Rc`,
+    // which the ownership API has no way to prevent.
+    #[derive(Default)]
+    struct Keeper(RefCell<Option<Rc<Gadget<Keeper>>>>);
+
+    impl PayloadEdges for Keeper {
+        fn strong_edges(&self) -> Vec<Rc<dyn StrongEdges>> {
+            self.0.borrow().strong_edges()
+        }
+    }
+
+    #[test]
+    fn hierarchy_and_co_owners_are_acyclic() {
+        let org = Owner::new("org");
+        let team = Owner::new("team");
+        org.add_child(&team);
+        let gadget = team.add_gadget(1);
+        gadget.add_co_owner(&org);
+
+        let roots: Vec<Rc<dyn StrongEdges>> = vec![org, team, gadget];
+        assert_eq!(find_strong_cycle(&roots), None);
+        assert_acyclic(&roots);
+    }
+
+    #[test]
+    fn cycle_through_a_payload_is_reported_with_its_path() {
+        let owner = Owner::with_data("leaky", Keeper::default());
+        let gadget = owner.add_gadget(1);
+        *owner.data().0.borrow_mut() = Some(Rc::clone(&gadget));
+
+        let roots: Vec<Rc<dyn StrongEdges>> = vec![Rc::clone(&owner) as Rc<dyn StrongEdges>];
+        let cycle = find_strong_cycle(&roots).expect("the payload closes a cycle");
+        assert_eq!(cycle.path, ["Owner(leaky)", "Gadget(1)", "Owner(leaky)"]);
+
+        let panic = panic::catch_unwind(AssertUnwindSafe(|| assert_acyclic(&roots)))
+            .expect_err("assert_acyclic passed on a cycle");
+        assert_eq!(
+            panic.downcast_ref::<String>().map(String::as_str),
+            Some("strong Rc cycle: Owner(leaky) -> Gadget(1) -> Owner(leaky)")
+        );
+
+        // Break the cycle so the test itself does not leak.
+        owner.data().0.take();
+        assert_eq!(find_strong_cycle(&roots), None);
+    }
+}
@@ -0,0 +1,217 @@
+// A structured snapshot of the reference counts around one owner,
+// instead of `Rc::strong_count` prints sprinkled through the code.
+
+use std::cell::RefCell;
+use std::fmt;
+use std::rc::Rc;
+
+use crate::json::{ToJson, Value};
+use crate::owner::Owner;
+
+/// Whether a `RefCell` was borrowed when the report was taken.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BorrowState {
+    Unused,
+    Shared,
+    Exclusive,
+}
+
+impl BorrowState {
+    pub(crate) fn of<T>(cell: &RefCell<T>) -> BorrowState {
+        if cell.try_borrow_mut().is_ok() {
+            BorrowState::Unused
+        } else if cell.try_borrow().is_ok() {
+            BorrowState::Shared
+        } else {
+            BorrowState::Exclusive
+        }
+    }
+
+    fn as_str(self) -> &'static str {
+        match self {
+            BorrowState::Unused => "unused",
+            BorrowState::Shared => "shared",
+            BorrowState::Exclusive => "exclusive",
+        }
+    }
+}
+
+impl fmt::Display for BorrowState {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// Reference counts of one live gadget.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct GadgetCounts {
+    pub id: i32,
+    pub strong: usize,
+    pub weak: usize,
+}
+
+/// An entry of the gadget list whose gadget has been dropped.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DanglingEntry {
+    /// Position of the entry in the gadget list.
+    pub position: usize,
+    /// The id the gadget was registered under, if the index still remembers it.
+    pub id: Option<i32>,
+}
+
+/// Reference-count report for an [`Owner`], see [`Owner::diagnostics`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Diagnostics {
+    pub name: String,
+    pub strong: usize,
+    pub weak: usize,
+    /// Borrow state of the owner's gadget list.
+    ///
+    /// While it is borrowed exclusively the list cannot be read,
+    /// and `gadgets` and `dangling` are left empty.
+    pub borrow_state: BorrowState,
+    pub gadgets: Vec<GadgetCounts>,
+    pub dangling: Vec<DanglingEntry>,
+}
+
+impl<O, G> Owner<O, G> {
+    /// Takes a snapshot of the reference counts of this owner and its gadgets.
+    ///
+    /// The counts of the owner include the `self` handle passed in,
+    /// and upgrading each gadget for inspection is not counted.
+    pub fn diagnostics(self: &Rc<Self>) -> Diagnostics {
+        let borrow_state = self.gadget_list().borrow_state();
+        let mut gadgets = vec![];
+        let mut dangling = vec![];
+        if let Ok(list) = self.gadget_list().try_borrow() {
+            for (position, entry) in list.entries().enumerate() {
+                match entry.upgrade() {
+                    Some(gadget) => gadgets.push(GadgetCounts {
+                        id: gadget.id(),
+                        strong: Rc::strong_count(&gadget) - 1,
+                        weak: Rc::weak_count(&gadget),
+                    }),
+                    None => dangling.push(DanglingEntry {
+                        position,
+                        id: list.id_of(entry),
+                    }),
+                }
+            }
+        }
+
+        Diagnostics {
+            name: self.name(),
+            strong: Rc::strong_count(self),
+            weak: Rc::weak_count(self),
+            borrow_state,
+            gadgets,
+            dangling,
+        }
+    }
+}
+
+impl Diagnostics {
+    /// Renders the report as a single-line JSON object for structured logs.
+    ///
+    /// Log consumers rely on this format, which stays as follows:
+    /// `{"name":..,"strong":..,"weak":..,"borrow_state":"unused"|"shared"|"exclusive",
+    /// "gadgets":[{"id":..,"strong":..,"weak":..}],"dangling":[{"position":..,"id":..|null}]}`
+    /// without whitespace, keys in that order.
+    pub fn to_json_line(&self) -> String {
+        ToJson::to_json(self).to_string()
+    }
+}
+
+impl ToJson for Diagnostics {
+    fn to_json(&self) -> Value {
+        let gadgets = self
+            .gadgets
+            .iter()
+            .map(|gadget| {
+                Value::Object(vec![
+                    ("id".to_string(), gadget.id.to_json()),
+                    ("strong".to_string(), gadget.strong.to_json()),
+                    ("weak".to_string(), gadget.weak.to_json()),
+                ])
+            })
+            .collect();
+        let dangling = self
+            .dangling
+            .iter()
+            .map(|entry| {
+                Value::Object(vec![
+                    ("position".to_string(), entry.position.to_json()),
+                    ("id".to_string(), entry.id.to_json()),
+                ])
+            })
+            .collect();
+
+        Value::Object(vec![
+            ("name".to_string(), self.name.to_json()),
+            ("strong".to_string(), self.strong.to_json()),
+            ("weak".to_string(), self.weak.to_json()),
+            (
+                "borrow_state".to_string(),
+                Value::String(self.borrow_state.to_string()),
+            ),
+            ("gadgets".to_string(), Value::Array(gadgets)),
+            ("dangling".to_string(), Value::Array(dangling)),
+        ])
+    }
+}
+
+impl fmt::Display for Diagnostics {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "Owner {:?}: strong={} weak={}, gadget list {}",
+            self.name, self.strong, self.weak, self.borrow_state
+        )?;
+        for gadget in &self.gadgets {
+            writeln!(
+                f,
+                "  gadget {}: strong={} weak={}",
+                gadget.id, gadget.strong, gadget.weak
+            )?;
+        }
+        for entry in &self.dangling {
+            match entry.id {
+                Some(id) => writeln!(f, "  dangling entry #{} (gadget {id})", entry.position)?,
+                None => writeln!(f, "  dangling entry #{}", entry.position)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn log_format_is_stable() {
+        let owner = Owner::new("log \"quoted\"");
+        let kept = owner.add_gadget(1);
+        let dropped = owner.add_gadget(2);
+        // Dropping the gadget while the list is borrowed leaves a dangling entry.
+        let list = owner.gadget_list().try_borrow().unwrap();
+        drop(dropped);
+        drop(list);
+
+        let diagnostics = owner.diagnostics();
+        assert_eq!(
+            diagnostics.to_json_line(),
+            concat!(
+                r#"{"name":"log \"quoted\"","strong":2,"weak":0,"borrow_state":"unused","#,
+                r#""gadgets":[{"id":1,"strong":1,"weak":2}],"#,
+                r#""dangling":[{"position":1,"id":2}]}"#,
+            )
+        );
+        assert_eq!(
+            diagnostics.to_string(),
+            "Owner \"log \\\"quoted\\\"\": strong=2 weak=0, gadget list unused\n  \
+             gadget 1: strong=1 weak=2\n  dangling entry #1 (gadget 2)\n"
+        );
+        drop(kept);
+    }
+}
@@ -0,0 +1,75 @@
+use std::fmt;
+use std::panic::Location;
+
+/// Errors reported by the ownership API.
+///
+/// Every panicking operation has a `try_` counterpart returning this error,
+/// so callers can recover instead of aborting.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Error {
+    /// A live gadget with this id is already registered on the owner.
+    DuplicateId(i32),
+    /// No gadget was ever registered under this id.
+    UnknownId(i32),
+    /// The gadget registered under this id has been dropped.
+    DanglingGadget(i32),
+    /// A `RefCell` needed by the operation is already borrowed,
+    /// typically because the call re-entered the owner.
+    AlreadyBorrowed {
+        /// Which field of the owner or gadget is borrowed, e.g. `"gadget list"`.
+        cell: &'static str,
+        /// With the `borrow-tracking` feature, the call sites of the outstanding
+        /// borrows; otherwise empty.
+        held_at: Vec<&'static Location<'static>>,
+    },
+    /// The gadget does not belong to the owner the operation was called on.
+    OwnerMismatch,
+    /// The operation would leave a gadget without any owner.
+    LastOwner,
+    /// Nesting the owner would make it its own ancestor.
+    HierarchyCycle,
+    /// The owner is not nested directly under the one the operation was called on.
+    NotAChild,
+    /// The owner id does not belong to the registry.
+    UnknownOwner,
+    /// The owner cannot be removed while live gadgets still list it.
+    OwnerInUse,
+    /// A JSON document could not be parsed or does not describe the expected data.
+    InvalidJson(String),
+    /// A binary snapshot is truncated, corrupted or of an unsupported version.
+    InvalidSnapshot(String),
+    /// A CSV row could not be parsed.
+    InvalidCsv(String),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::DuplicateId(id) => write!(f, "gadget id {id} is already registered"),
+            Error::UnknownId(id) => write!(f, "no gadget is registered under id {id}"),
+            Error::DanglingGadget(id) => write!(f, "gadget {id} has been dropped"),
+            Error::AlreadyBorrowed { cell, held_at } => {
+                write!(f, "{cell} is already borrowed")?;
+                for (i, location) in held_at.iter().enumerate() {
+                    let separator = if i == 0 { " (held at " } else { ", " };
+                    write!(f, "{separator}{location}")?;
+                }
+                if !held_at.is_empty() {
+                    f.write_str(")")?;
+                }
+                Ok(())
+            }
+            Error::OwnerMismatch => f.write_str("gadget belongs to a different owner"),
+            Error::LastOwner => f.write_str("gadget cannot be left without an owner"),
+            Error::HierarchyCycle => f.write_str("owner cannot be nested under itself"),
+            Error::NotAChild => f.write_str("owner is not a child of this owner"),
+            Error::UnknownOwner => f.write_str("owner is not in the registry"),
+            Error::OwnerInUse => f.write_str("owner still has live gadgets"),
+            Error::InvalidJson(message) => write!(f, "invalid JSON: {message}"),
+            Error::InvalidSnapshot(message) => write!(f, "invalid snapshot: {message}"),
+            Error::InvalidCsv(message) => write!(f, "invalid CSV: {message}"),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
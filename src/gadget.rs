@@ -0,0 +1,269 @@
+use std::cell::Cell;
+use std::collections::BTreeMap;
+use std::rc::Rc;
+
+use crate::error::Error;
+use crate::owner::{fail, Event, Owner};
+use crate::tracked_cell::TrackedRefCell;
+
+mod attributes;
+
+pub use attributes::GadgetStatus;
+
+/// An item held by one or more [`Owner`]s.
+///
+/// A gadget keeps every owner alive through a strong `Rc<Owner>`,
+/// while each owner only lists it through a `Weak<Gadget>`.
+/// So a co-owner is never dropped from under a gadget: it lives at least
+/// as long as the gadget, and stops owning it only through
+/// [`Gadget::remove_co_owner`] or [`transfer`](crate::transfer).
+pub struct Gadget<O = (), G = ()> {
+    id: i32,
+    data: G,
+    // Every owner of this gadget, the primary owner first.
+    // Most gadgets have exactly one; shared equipment lists its co-owners after it.
+    //
+    // Each of them is a strong `Rc<Owner>`, and each of them lists this gadget
+    // through a `Weak<Gadget>`, so co-ownership adds no `Rc` cycle either.
+    // Behind a RefCell so that `transfer` and the co-owner operations can
+    // edit the set through the shared `Rc<Gadget>` handed out to callers.
+    owners: TrackedRefCell<Vec<Rc<Owner<O, G>>>>,
+    status: Cell<GadgetStatus>,
+    label: TrackedRefCell<String>,
+    tags: TrackedRefCell<BTreeMap<String, String>>,
+}
+
+impl<O, G> Gadget<O, G> {
+    pub(crate) fn new(id: i32, data: G, owner: Rc<Owner<O, G>>) -> Self {
+        Gadget {
+            id,
+            data,
+            owners: TrackedRefCell::new("gadget owners", vec![owner]),
+            status: Cell::new(GadgetStatus::default()),
+            label: TrackedRefCell::new("gadget label", String::new()),
+            tags: TrackedRefCell::new("gadget tags", BTreeMap::new()),
+        }
+    }
+
+    pub fn id(&self) -> i32 {
+        self.id
+    }
+
+    pub fn data(&self) -> &G {
+        &self.data
+    }
+
+    /// Returns the primary owner of this gadget.
+    #[track_caller]
+    pub fn owner(&self) -> Rc<Owner<O, G>> {
+        Rc::clone(&self.owners.borrow()[0])
+    }
+
+    /// Returns every owner of this gadget, the primary owner first.
+    #[track_caller]
+    pub fn owners(&self) -> Vec<Rc<Owner<O, G>>> {
+        self.owners.borrow().clone()
+    }
+
+    /// Returns whether `owner` is the primary owner or a co-owner of this gadget.
+    #[track_caller]
+    pub fn is_owned_by(&self, owner: &Rc<Owner<O, G>>) -> bool {
+        self.owners
+            .borrow()
+            .iter()
+            .any(|candidate| Rc::ptr_eq(candidate, owner))
+    }
+
+    pub(crate) fn owners_cell(&self) -> &TrackedRefCell<Vec<Rc<Owner<O, G>>>> {
+        &self.owners
+    }
+
+    /// Adds `owner` as a co-owner and lists this gadget in its gadget list.
+    ///
+    /// The gadget then holds a strong `Rc` to `owner`, like to its primary owner:
+    /// dropping every other handle to a co-owner does not drop it
+    /// while this gadget is alive.
+    /// Adding an existing owner again does nothing.
+    /// Fails with [`Error::DuplicateId`] if `owner` already has a different
+    /// live gadget with the same id.
+    #[track_caller]
+    pub fn try_add_co_owner(self: &Rc<Self>, owner: &Rc<Owner<O, G>>) -> Result<(), Error> {
+        let mut owners = self.owners.try_borrow_mut()?;
+        if owners.iter().any(|candidate| Rc::ptr_eq(candidate, owner)) {
+            return Ok(());
+        }
+
+        let mut gadgets = owner.gadget_list().try_borrow_mut()?;
+        if gadgets
+            .get(self.id)
+            .is_some_and(|entry| entry.strong_count() > 0)
+        {
+            return Err(Error::DuplicateId(self.id));
+        }
+        gadgets.push(self.id, Rc::downgrade(self));
+        owners.push(Rc::clone(owner));
+        drop(gadgets);
+        drop(owners);
+        owner.emit(Event::GadgetAdded { id: self.id });
+        Ok(())
+    }
+
+    /// Like [`Gadget::try_add_co_owner`], but panics on failure.
+    #[track_caller]
+    pub fn add_co_owner(self: &Rc<Self>, owner: &Rc<Owner<O, G>>) {
+        self.try_add_co_owner(owner).unwrap_or_else(fail)
+    }
+
+    /// Removes `owner` from the owners of this gadget and from its gadget list.
+    ///
+    /// Removing the primary owner promotes the first co-owner in its place.
+    /// `owner` receives [`Event::GadgetRemoved`].
+    /// Fails with [`Error::OwnerMismatch`] if `owner` does not own this gadget,
+    /// and with [`Error::LastOwner`] if it is the only one left:
+    /// a gadget always has at least one owner.
+    #[track_caller]
+    pub fn try_remove_co_owner(&self, owner: &Rc<Owner<O, G>>) -> Result<(), Error> {
+        let mut owners = self.owners.try_borrow_mut()?;
+        let position = owners
+            .iter()
+            .position(|candidate| Rc::ptr_eq(candidate, owner))
+            .ok_or(Error::OwnerMismatch)?;
+        if owners.len() == 1 {
+            return Err(Error::LastOwner);
+        }
+
+        owner.gadget_list().try_borrow_mut()?.remove(self.id, self);
+        // `owner` is still borrowed by the caller, so this cannot free it.
+        owners.remove(position);
+        drop(owners);
+        owner.emit(Event::GadgetRemoved { id: self.id });
+        Ok(())
+    }
+
+    /// Like [`Gadget::try_remove_co_owner`], but panics on failure.
+    #[track_caller]
+    pub fn remove_co_owner(&self, owner: &Rc<Owner<O, G>>) {
+        self.try_remove_co_owner(owner).unwrap_or_else(fail)
+    }
+}
+
+impl<O, G> Drop for Gadget<O, G> {
+    // The owners outlive this call: we still hold a strong `Rc<Owner>` to each,
+    // so it is always safe to reach back into their gadget lists here.
+    fn drop(&mut self) {
+        for owner in self.owners.borrow().iter() {
+            owner.deregister(self);
+            owner.emit(Event::GadgetDropped { id: self.id });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn names(gadget: &Gadget) -> Vec<String> {
+        gadget.owners().iter().map(|owner| owner.name()).collect()
+    }
+
+    #[test]
+    fn removing_the_primary_owner_promotes_the_first_co_owner() {
+        let first = Owner::new("first");
+        let second = Owner::new("second");
+        let third = Owner::new("third");
+        let gadget = first.add_gadget(1);
+        gadget.add_co_owner(&second);
+        gadget.add_co_owner(&third);
+
+        gadget.remove_co_owner(&first);
+
+        assert_eq!(names(&gadget), ["second", "third"]);
+        assert!(Rc::ptr_eq(&gadget.owner(), &second));
+        assert!(first.gadget(1).is_none());
+        assert_eq!(first.gadgets().len(), 0);
+        assert!(Rc::ptr_eq(&second.gadget(1).unwrap(), &gadget));
+        assert!(Rc::ptr_eq(&third.gadget(1).unwrap(), &gadget));
+    }
+
+    #[test]
+    fn removing_a_co_owner_keeps_the_primary_owner() {
+        let primary = Owner::new("primary");
+        let co_owner = Owner::new("co");
+        let gadget = primary.add_gadget(1);
+        gadget.add_co_owner(&co_owner);
+
+        gadget.remove_co_owner(&co_owner);
+
+        assert_eq!(names(&gadget), ["primary"]);
+        assert!(co_owner.gadget(1).is_none());
+        assert!(!gadget.is_owned_by(&co_owner));
+        assert_eq!(Rc::strong_count(&co_owner), 1);
+    }
+
+    #[test]
+    fn the_last_owner_cannot_be_removed() {
+        let owner = Owner::new("owner");
+        let gadget = owner.add_gadget(1);
+
+        assert_eq!(gadget.try_remove_co_owner(&owner), Err(Error::LastOwner));
+        assert_eq!(names(&gadget), ["owner"]);
+        assert!(Rc::ptr_eq(&owner.gadget(1).unwrap(), &gadget));
+    }
+
+    #[test]
+    fn only_owners_can_be_removed() {
+        let owner = Owner::new("owner");
+        let stranger = Owner::new("stranger");
+        let gadget = owner.add_gadget(1);
+        gadget.add_co_owner(&Owner::new("co"));
+
+        assert_eq!(
+            gadget.try_remove_co_owner(&stranger),
+            Err(Error::OwnerMismatch)
+        );
+        assert_eq!(names(&gadget), ["owner", "co"]);
+    }
+
+    #[test]
+    fn co_owners_live_as_long_as_the_gadget() {
+        let owner = Owner::new("owner");
+        let gadget = owner.add_gadget(1);
+        let co_owner = Owner::new("co");
+        gadget.add_co_owner(&co_owner);
+        let weak = Rc::downgrade(&co_owner);
+
+        drop(co_owner);
+        assert_eq!(
+            weak.upgrade().map(|owner| owner.name()).as_deref(),
+            Some("co")
+        );
+        drop(gadget);
+        assert!(weak.upgrade().is_none());
+    }
+
+    #[test]
+    fn adding_a_co_owner_twice_does_nothing() {
+        let owner = Owner::new("owner");
+        let co_owner = Owner::new("co");
+        let gadget = owner.add_gadget(1);
+        gadget.add_co_owner(&co_owner);
+        gadget.add_co_owner(&co_owner);
+
+        assert_eq!(names(&gadget), ["owner", "co"]);
+        assert_eq!(co_owner.gadgets().len(), 1);
+    }
+
+    #[test]
+    fn co_owner_with_the_same_id_is_rejected() {
+        let owner = Owner::new("owner");
+        let co_owner = Owner::new("co");
+        let gadget = owner.add_gadget(1);
+        let _taken = co_owner.add_gadget(1);
+
+        assert_eq!(
+            gadget.try_add_co_owner(&co_owner),
+            Err(Error::DuplicateId(1))
+        );
+        assert_eq!(names(&gadget), ["owner"]);
+    }
+}
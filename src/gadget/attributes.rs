@@ -0,0 +1,59 @@
+use std::collections::BTreeMap;
+
+use super::Gadget;
+
+/// Where a gadget is in its service life.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum GadgetStatus {
+    #[default]
+    Active,
+    Retired,
+    Lost,
+}
+
+// Attributes live in `Cell`s and `RefCell`s, so they can be edited through
+// the shared `Rc<Gadget>` returned by `upgrade()`.
+// Getters hand out copies: no borrow outlives the call.
+impl<O, G> Gadget<O, G> {
+    pub fn status(&self) -> GadgetStatus {
+        self.status.get()
+    }
+
+    pub fn set_status(&self, status: GadgetStatus) {
+        self.status.set(status);
+    }
+
+    #[track_caller]
+    pub fn label(&self) -> String {
+        self.label.borrow().clone()
+    }
+
+    #[track_caller]
+    pub fn set_label(&self, label: impl Into<String>) {
+        *self.label.borrow_mut() = label.into();
+    }
+
+    /// Returns the value of the tag `key`, if set.
+    #[track_caller]
+    pub fn tag(&self, key: &str) -> Option<String> {
+        self.tags.borrow().get(key).cloned()
+    }
+
+    /// Sets the tag `key` to `value`, returning its previous value.
+    #[track_caller]
+    pub fn set_tag(&self, key: impl Into<String>, value: impl Into<String>) -> Option<String> {
+        self.tags.borrow_mut().insert(key.into(), value.into())
+    }
+
+    /// Removes the tag `key`, returning its value.
+    #[track_caller]
+    pub fn remove_tag(&self, key: &str) -> Option<String> {
+        self.tags.borrow_mut().remove(key)
+    }
+
+    /// Returns all tags, ordered by key.
+    #[track_caller]
+    pub fn tags(&self) -> BTreeMap<String, String> {
+        self.tags.borrow().clone()
+    }
+}
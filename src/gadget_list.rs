@@ -0,0 +1,141 @@
+use std::collections::HashMap;
+use std::{rc, sync};
+
+// The contents of `Owner::gadgets`, shared by the `Rc` and the `sync` owners.
+//
+// `entries` keeps registration order, `by_id` answers lookups by `Gadget::id`
+// and `positions` finds the entry of a gadget by address, so removing it on drop is O(1).
+// Removal leaves a `None` hole in `entries` to keep the order of the others;
+// holes are compacted away once they make up half of the vector.
+// All of them only hold `Weak` pointers (or addresses, which those `Weak`s keep
+// from being reused) and are always updated together,
+// behind the single `RefCell` or `RwLock` of the owner.
+pub(crate) struct GadgetList<W> {
+    entries: Vec<Option<W>>,
+    positions: HashMap<usize, usize>,
+    holes: usize,
+    by_id: HashMap<i32, W>,
+}
+
+// The `Weak` pointer type stored in a `GadgetList`.
+pub(crate) trait WeakEntry: Clone {
+    type Target;
+
+    fn as_ptr(&self) -> *const Self::Target;
+
+    fn strong_count(&self) -> usize;
+}
+
+impl<T> WeakEntry for rc::Weak<T> {
+    type Target = T;
+
+    fn as_ptr(&self) -> *const T {
+        rc::Weak::as_ptr(self)
+    }
+
+    fn strong_count(&self) -> usize {
+        rc::Weak::strong_count(self)
+    }
+}
+
+impl<T> WeakEntry for sync::Weak<T> {
+    type Target = T;
+
+    fn as_ptr(&self) -> *const T {
+        sync::Weak::as_ptr(self)
+    }
+
+    fn strong_count(&self) -> usize {
+        sync::Weak::strong_count(self)
+    }
+}
+
+impl<W: WeakEntry> GadgetList<W> {
+    pub(crate) fn new() -> Self {
+        GadgetList {
+            entries: vec![],
+            positions: HashMap::new(),
+            holes: 0,
+            by_id: HashMap::new(),
+        }
+    }
+
+    // The registered entries, in registration order.
+    pub(crate) fn entries(&self) -> impl Iterator<Item = &W> {
+        self.entries.iter().flatten()
+    }
+
+    pub(crate) fn len(&self) -> usize {
+        self.entries.len() - self.holes
+    }
+
+    pub(crate) fn get(&self, id: i32) -> Option<&W> {
+        self.by_id.get(&id)
+    }
+
+    // Finds the id an entry was registered under, even after the gadget was dropped.
+    pub(crate) fn id_of(&self, entry: &W) -> Option<i32> {
+        self.by_id
+            .iter()
+            .find(|(_, indexed)| std::ptr::eq(indexed.as_ptr(), entry.as_ptr()))
+            .map(|(id, _)| *id)
+    }
+
+    pub(crate) fn push(&mut self, id: i32, gadget: W) {
+        self.by_id.insert(id, gadget.clone());
+        self.positions
+            .insert(gadget.as_ptr() as usize, self.entries.len());
+        self.entries.push(Some(gadget));
+    }
+
+    // Removes the entry pointing at the gadget registered under `id` at `gadget`,
+    // whether or not it is still alive.
+    pub(crate) fn remove(&mut self, id: i32, gadget: *const W::Target) {
+        if let Some(position) = self.positions.remove(&(gadget as usize)) {
+            self.entries[position] = None;
+            self.holes += 1;
+            if self.holes * 2 >= self.entries.len() {
+                self.compact();
+            }
+        }
+        if self
+            .by_id
+            .get(&id)
+            .is_some_and(|entry| std::ptr::eq(entry.as_ptr(), gadget))
+        {
+            self.by_id.remove(&id);
+        }
+    }
+
+    pub(crate) fn dangling_count(&self) -> usize {
+        self.entries()
+            .filter(|gadget| gadget.strong_count() == 0)
+            .count()
+    }
+
+    // Drops every dangling entry and returns how many were removed.
+    pub(crate) fn prune(&mut self) -> usize {
+        let before = self.len();
+        for entry in &mut self.entries {
+            if entry
+                .as_ref()
+                .is_some_and(|gadget| gadget.strong_count() == 0)
+            {
+                *entry = None;
+            }
+        }
+        self.compact();
+        self.by_id.retain(|_, gadget| gadget.strong_count() > 0);
+        before - self.len()
+    }
+
+    // Closes the holes left by removed entries and renumbers `positions`.
+    fn compact(&mut self) {
+        self.entries.retain(Option::is_some);
+        self.holes = 0;
+        self.positions.clear();
+        for (position, gadget) in self.entries.iter().flatten().enumerate() {
+            self.positions.insert(gadget.as_ptr() as usize, position);
+        }
+    }
+}
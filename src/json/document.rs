@@ -0,0 +1,358 @@
+// The JSON form of an owner with its gadgets.
+//
+// `Rc<Owner>` and `Weak<Gadget>` cannot be written out directly:
+// following them naively would either loop forever or duplicate objects.
+// Instead every owner gets a numeric `ref`, and gadgets list their owners by ref:
+//
+//     {"version":1,"owner":0,
+//      "owners":[{"ref":0,"name":"Gadget Man","data":null}],
+//      "gadgets":[{"id":1,"owners":[0],"data":null,
+//                  "status":"active","label":"","tags":{}}]}
+//
+// Co-owners of the exported gadgets are written as well, so the import
+// can rebuild the same `Rc`/`Weak` topology. Dead `Weak`s are skipped.
+
+use std::collections::HashMap;
+use std::rc::Rc;
+
+use super::{invalid, FromJson, ToJson, Value};
+use crate::error::Error;
+use crate::gadget::{Gadget, GadgetStatus};
+use crate::owner::{fail, Owner};
+
+const VERSION: i64 = 1;
+
+/// The result of [`Owner::from_json`].
+///
+/// Owners only hold `Weak` pointers to their gadgets,
+/// so the strong handles of the imported gadgets are returned here;
+/// dropping them drops the gadgets.
+pub struct ImportedOwner<O = (), G = ()> {
+    /// The owner the document was exported from.
+    pub owner: Rc<Owner<O, G>>,
+    /// Every owner in the document, including `owner` and the co-owners of its gadgets.
+    pub owners: Vec<Rc<Owner<O, G>>>,
+    pub gadgets: Vec<Rc<Gadget<O, G>>>,
+}
+
+fn status_name(status: GadgetStatus) -> &'static str {
+    match status {
+        GadgetStatus::Active => "active",
+        GadgetStatus::Retired => "retired",
+        GadgetStatus::Lost => "lost",
+    }
+}
+
+fn parse_status(name: &str) -> Result<GadgetStatus, Error> {
+    match name {
+        "active" => Ok(GadgetStatus::Active),
+        "retired" => Ok(GadgetStatus::Retired),
+        "lost" => Ok(GadgetStatus::Lost),
+        _ => Err(invalid(format!("unknown gadget status {name:?}"))),
+    }
+}
+
+fn number(value: i64) -> Value {
+    Value::Number(value.to_string())
+}
+
+fn field<'a>(value: &'a Value, key: &str) -> Result<&'a Value, Error> {
+    value
+        .get(key)
+        .ok_or_else(|| invalid(format!("missing field {key:?}")))
+}
+
+fn integer_field(value: &Value, key: &str) -> Result<i64, Error> {
+    field(value, key)?
+        .as_i64()
+        .ok_or_else(|| invalid(format!("field {key:?} must be an integer")))
+}
+
+impl<O: ToJson, G: ToJson> Owner<O, G> {
+    /// Exports this owner and its live gadgets as a JSON document.
+    ///
+    /// Fails with [`Error::AlreadyBorrowed`] if a gadget list is in use.
+    pub fn try_to_json(&self) -> Result<String, Error> {
+        let mut owners: Vec<Rc<Owner<O, G>>> = vec![];
+        let mut owner_json = vec![owner_value(0, self)];
+        let mut gadget_json = vec![];
+
+        for gadget in self.try_live_gadgets()? {
+            let mut refs = vec![];
+            for owner in gadget.owners() {
+                let reference = if std::ptr::eq(Rc::as_ptr(&owner), self) {
+                    0
+                } else if let Some(i) = owners.iter().position(|known| Rc::ptr_eq(known, &owner)) {
+                    i as i64 + 1
+                } else {
+                    owners.push(Rc::clone(&owner));
+                    owner_json.push(owner_value(owners.len() as i64, &owner));
+                    owners.len() as i64
+                };
+                refs.push(number(reference));
+            }
+
+            gadget_json.push(Value::Object(vec![
+                ("id".to_string(), number(gadget.id().into())),
+                ("owners".to_string(), Value::Array(refs)),
+                ("data".to_string(), gadget.data().to_json()),
+                (
+                    "status".to_string(),
+                    Value::String(status_name(gadget.status()).to_string()),
+                ),
+                ("label".to_string(), Value::String(gadget.label())),
+                ("tags".to_string(), gadget.tags().to_json()),
+            ]));
+        }
+
+        let document = Value::Object(vec![
+            ("version".to_string(), number(VERSION)),
+            ("owner".to_string(), number(0)),
+            ("owners".to_string(), Value::Array(owner_json)),
+            ("gadgets".to_string(), Value::Array(gadget_json)),
+        ]);
+        Ok(document.to_string())
+    }
+
+    /// Like [`Owner::try_to_json`], but panics on failure.
+    pub fn to_json(&self) -> String {
+        self.try_to_json().unwrap_or_else(fail)
+    }
+}
+
+fn owner_value<O: ToJson, G>(reference: i64, owner: &Owner<O, G>) -> Value {
+    Value::Object(vec![
+        ("ref".to_string(), number(reference)),
+        ("name".to_string(), Value::String(owner.name())),
+        ("data".to_string(), owner.data().to_json()),
+    ])
+}
+
+impl<O: FromJson, G: FromJson> Owner<O, G> {
+    /// Rebuilds an owner and its gadgets from a document written by [`Owner::to_json`].
+    ///
+    /// Fails with [`Error::InvalidJson`] if the document is malformed,
+    /// or with [`Error::DuplicateId`] if two gadgets of one owner share an id.
+    pub fn from_json(text: &str) -> Result<ImportedOwner<O, G>, Error> {
+        let document = Value::parse(text)?;
+        let version = integer_field(&document, "version")?;
+        if version != VERSION {
+            return Err(invalid(format!("unsupported version {version}")));
+        }
+
+        let mut owners = vec![];
+        let mut by_ref = HashMap::new();
+        for value in field(&document, "owners")?
+            .as_array()
+            .ok_or_else(|| invalid("\"owners\" must be an array"))?
+        {
+            let reference = integer_field(value, "ref")?;
+            let name = field(value, "name")?
+                .as_str()
+                .ok_or_else(|| invalid("owner name must be a string"))?;
+            let owner = Owner::with_data(name, O::from_json(field(value, "data")?)?);
+            if by_ref.insert(reference, Rc::clone(&owner)).is_some() {
+                return Err(invalid(format!("duplicate owner ref {reference}")));
+            }
+            owners.push(owner);
+        }
+        let lookup = |reference: i64| {
+            by_ref
+                .get(&reference)
+                .cloned()
+                .ok_or_else(|| invalid(format!("unknown owner ref {reference}")))
+        };
+        let owner = lookup(integer_field(&document, "owner")?)?;
+
+        let mut gadgets = vec![];
+        for value in field(&document, "gadgets")?
+            .as_array()
+            .ok_or_else(|| invalid("\"gadgets\" must be an array"))?
+        {
+            let id = i32::try_from(integer_field(value, "id")?)
+                .map_err(|_| invalid("gadget id out of range"))?;
+            let refs = field(value, "owners")?
+                .as_array()
+                .ok_or_else(|| invalid("gadget owners must be an array"))?;
+            let (primary, co_owners) = refs
+                .split_first()
+                .ok_or_else(|| invalid(format!("gadget {id} has no owner")))?;
+            let reference = |value: &Value| {
+                value
+                    .as_i64()
+                    .ok_or_else(|| invalid("owner ref must be an integer"))
+            };
+
+            let gadget = lookup(reference(primary)?)?
+                .try_add_gadget_with(id, G::from_json(field(value, "data")?)?)?;
+            for co_owner in co_owners {
+                gadget.try_add_co_owner(&lookup(reference(co_owner)?)?)?;
+            }
+            if let Some(status) = value.get("status") {
+                let status = status
+                    .as_str()
+                    .ok_or_else(|| invalid("gadget status must be a string"))?;
+                gadget.set_status(parse_status(status)?);
+            }
+            if let Some(label) = value.get("label") {
+                gadget.set_label(String::from_json(label)?);
+            }
+            if let Some(tags) = value.get("tags") {
+                for (key, tag) in std::collections::BTreeMap::<String, String>::from_json(tags)? {
+                    gadget.set_tag(key, tag);
+                }
+            }
+            gadgets.push(gadget);
+        }
+
+        Ok(ImportedOwner {
+            owner,
+            owners,
+            gadgets,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn names(owners: &[Rc<Owner>]) -> Vec<String> {
+        owners.iter().map(|owner| owner.name()).collect()
+    }
+
+    fn ids(owner: &Owner) -> Vec<i32> {
+        owner.live_gadgets().map(|gadget| gadget.id()).collect()
+    }
+
+    #[test]
+    fn round_trip_rebuilds_co_owners_and_attributes() {
+        let owner = Owner::new("Ann");
+        let co_owner = Owner::new("Bob \"the builder\"\n");
+        let shared = owner.add_gadget(1);
+        shared.add_co_owner(&co_owner);
+        let lost = owner.add_gadget(2);
+        lost.set_status(GadgetStatus::Lost);
+        lost.set_label("back\\slash\ttab \u{1} é");
+        lost.set_tag("room", "4\"B\"");
+        let dropped = owner.add_gadget(3);
+        drop(dropped);
+
+        let text = owner.to_json();
+        assert!(text.contains(r#"back\\slash\ttab \u0001 é"#));
+        let imported = Owner::<(), ()>::from_json(&text).unwrap();
+
+        assert_eq!(imported.owner.name(), "Ann");
+        assert_eq!(names(&imported.owners), ["Ann", "Bob \"the builder\"\n"]);
+        assert_eq!(ids(&imported.owner), [1, 2]);
+        let bob = &imported.owners[1];
+        assert_eq!(ids(bob), [1]);
+
+        let shared = imported.owner.gadget(1).unwrap();
+        assert_eq!(names(&shared.owners()), ["Ann", "Bob \"the builder\"\n"]);
+        assert!(Rc::ptr_eq(&bob.gadget(1).unwrap(), &shared));
+
+        let lost = imported.owner.gadget(2).unwrap();
+        assert_eq!(lost.status(), GadgetStatus::Lost);
+        assert_eq!(lost.label(), "back\\slash\ttab \u{1} é");
+        assert_eq!(lost.tag("room").as_deref(), Some("4\"B\""));
+        assert_eq!(imported.gadgets.len(), 2);
+
+        // Exporting the import again gives the same document.
+        assert_eq!(imported.owner.to_json(), text);
+    }
+
+    #[test]
+    fn round_trip_keeps_payloads() {
+        let owner = Owner::with_data("Ann", 7_i64);
+        let _gadget = owner.add_gadget_with(1, "drill".to_string());
+
+        let imported = Owner::<i64, String>::from_json(&owner.to_json()).unwrap();
+        assert_eq!(*imported.owner.data(), 7);
+        assert_eq!(imported.gadgets[0].data(), "drill");
+    }
+
+    #[test]
+    fn imported_gadgets_are_dropped_with_their_handles() {
+        let owner = Owner::new("Ann");
+        let _gadget = owner.add_gadget(1);
+
+        let imported = Owner::<(), ()>::from_json(&owner.to_json()).unwrap();
+        let owner = Rc::clone(&imported.owner);
+        drop(imported);
+        assert_eq!(owner.live_gadgets().count(), 0);
+        assert_eq!(Rc::strong_count(&owner), 1);
+    }
+
+    fn import_error(text: &str) -> Error {
+        match Owner::<(), ()>::from_json(text) {
+            Ok(_) => panic!("{text} was accepted"),
+            Err(err) => err,
+        }
+    }
+
+    fn document(owners: &str, gadgets: &str) -> String {
+        format!(r#"{{"version":1,"owner":0,"owners":[{owners}],"gadgets":[{gadgets}]}}"#)
+    }
+
+    const ANN: &str = r#"{"ref":0,"name":"Ann","data":null}"#;
+
+    #[test]
+    fn malformed_documents_are_rejected() {
+        for text in ["", "{", "[1,2]", r#"{"version":1}"#, "{\"version\":1,}"] {
+            assert!(
+                matches!(import_error(text), Error::InvalidJson(_)),
+                "{text}"
+            );
+        }
+        for escape in [r"\u+041", r"\u-041", r"\u 041", r"\u04"] {
+            let owner = format!(r#"{{"ref":0,"name":"{escape}1","data":null}}"#);
+            match import_error(&document(&owner, "")) {
+                Error::InvalidJson(message) => {
+                    assert!(message.starts_with("invalid unicode escape"), "{message}")
+                }
+                err => panic!("{escape}: {err:?}"),
+            }
+        }
+        assert_eq!(
+            import_error(r#"{"version":2,"owner":0,"owners":[],"gadgets":[]}"#),
+            Error::InvalidJson("unsupported version 2".to_string())
+        );
+        assert_eq!(
+            import_error(&document(ANN, r#"{"id":1,"owners":[],"data":null}"#)),
+            Error::InvalidJson("gadget 1 has no owner".to_string())
+        );
+        assert_eq!(
+            import_error(&document(
+                ANN,
+                r#"{"id":1,"owners":[0],"data":null,"status":"broken"}"#
+            )),
+            Error::InvalidJson("unknown gadget status \"broken\"".to_string())
+        );
+    }
+
+    #[test]
+    fn bad_owner_refs_are_rejected() {
+        assert_eq!(
+            import_error(&document(&format!("{ANN},{ANN}"), "")),
+            Error::InvalidJson("duplicate owner ref 0".to_string())
+        );
+        assert_eq!(
+            import_error(&document(ANN, r#"{"id":1,"owners":[5],"data":null}"#)),
+            Error::InvalidJson("unknown owner ref 5".to_string())
+        );
+        assert_eq!(
+            import_error(r#"{"version":1,"owner":3,"owners":[],"gadgets":[]}"#),
+            Error::InvalidJson("unknown owner ref 3".to_string())
+        );
+    }
+
+    #[test]
+    fn duplicate_gadget_ids_are_rejected() {
+        let gadget = r#"{"id":1,"owners":[0],"data":null}"#;
+        assert_eq!(
+            import_error(&document(ANN, &format!("{gadget},{gadget}"))),
+            Error::DuplicateId(1)
+        );
+    }
+}
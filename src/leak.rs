@@ -0,0 +1,277 @@
+// The comments in `lib.rs` explain why an `Rc` cycle leaks.
+// This module turns that explanation into something a test can check:
+// track the owners and gadgets created in a scope through `Weak` pointers,
+// record their reference counts at checkpoints,
+// and assert that every one of them was freed once the scope has ended.
+
+use std::fmt;
+use std::rc::{Rc, Weak};
+
+use crate::cycles::StrongEdges;
+
+/// Strong and weak reference counts of a tracked allocation.
+///
+/// The weak count excludes the tracker's own `Weak` pointer.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct RefCounts {
+    pub strong: usize,
+    pub weak: usize,
+}
+
+impl fmt::Display for RefCounts {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "strong={} weak={}", self.strong, self.weak)
+    }
+}
+
+/// Reference counts of every tracked allocation at one point in time.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Checkpoint {
+    pub label: String,
+    pub counts: Vec<(String, RefCounts)>,
+}
+
+struct Tracked {
+    label: String,
+    counts: Box<dyn Fn() -> RefCounts>,
+}
+
+/// Records reference counts of owners, gadgets or any other `Rc` allocation
+/// without keeping them alive.
+#[derive(Default)]
+pub struct LeakTracker {
+    tracked: Vec<Tracked>,
+    checkpoints: Vec<Checkpoint>,
+}
+
+impl LeakTracker {
+    pub fn new() -> Self {
+        LeakTracker::default()
+    }
+
+    /// Starts tracking `rc` under `label`.
+    ///
+    /// Panics if `label` is already used: labels are how allocations are
+    /// told apart in [`LeakTracker::assert_counts`] and in leak reports.
+    pub fn track<T: ?Sized + 'static>(&mut self, label: impl Into<String>, rc: &Rc<T>) {
+        let label = label.into();
+        assert!(!self.is_tracked(&label), "{label} is already tracked");
+        let weak: Weak<T> = Rc::downgrade(rc);
+        self.tracked.push(Tracked {
+            label,
+            counts: Box::new(move || RefCounts {
+                strong: weak.strong_count(),
+                // `Weak::weak_count` is 0 once the value is gone,
+                // and counts our own pointer while it is still alive.
+                weak: weak.weak_count().saturating_sub(1),
+            }),
+        });
+    }
+
+    /// Starts tracking an owner, gadget or other graph node under its own label,
+    /// and returns the label used.
+    ///
+    /// Gadget ids are only unique per owner, so when the label is already taken
+    /// a suffix is added: the second `Gadget(1)` is tracked as `Gadget(1) #2`.
+    pub fn track_node<N: StrongEdges + 'static>(&mut self, node: &Rc<N>) -> String {
+        let base = node.label();
+        let mut label = base.clone();
+        let mut n = 1;
+        while self.is_tracked(&label) {
+            n += 1;
+            label = format!("{base} #{n}");
+        }
+        self.track(label.clone(), node);
+        label
+    }
+
+    fn is_tracked(&self, label: &str) -> bool {
+        self.tracked.iter().any(|tracked| tracked.label == label)
+    }
+
+    /// Returns the current counts of every tracked allocation.
+    pub fn counts(&self) -> Vec<(String, RefCounts)> {
+        self.tracked
+            .iter()
+            .map(|tracked| (tracked.label.clone(), (tracked.counts)()))
+            .collect()
+    }
+
+    /// Records the current counts under `label` and returns them.
+    pub fn checkpoint(&mut self, label: impl Into<String>) -> &Checkpoint {
+        let checkpoint = Checkpoint {
+            label: label.into(),
+            counts: self.counts(),
+        };
+        self.checkpoints.push(checkpoint);
+        self.checkpoints.last().unwrap()
+    }
+
+    pub fn checkpoints(&self) -> &[Checkpoint] {
+        &self.checkpoints
+    }
+
+    /// Returns the labels of tracked allocations whose value is still alive.
+    pub fn leaked(&self) -> Vec<String> {
+        self.counts()
+            .into_iter()
+            .filter(|(_, counts)| counts.strong > 0)
+            .map(|(label, _)| label)
+            .collect()
+    }
+
+    /// Panics if the counts of the allocation tracked under `label` differ from `expected`.
+    pub fn assert_counts(&self, label: &str, expected: RefCounts) {
+        let (_, actual) = self
+            .counts()
+            .into_iter()
+            .find(|(tracked, _)| tracked == label)
+            .unwrap_or_else(|| panic!("{label} is not tracked"));
+        assert_eq!(actual, expected, "reference counts of {label}");
+    }
+
+    /// Panics if any tracked allocation is still alive,
+    /// listing each leak together with its counts at every checkpoint.
+    pub fn assert_all_freed(&self) {
+        let leaked = self.leaked();
+        if leaked.is_empty() {
+            return;
+        }
+
+        let mut report = format!("{} allocation(s) still alive:", leaked.len());
+        for label in &leaked {
+            report.push_str(&format!("\n  {label}"));
+            for checkpoint in &self.checkpoints {
+                if let Some((_, counts)) = checkpoint.counts.iter().find(|(l, _)| l == label) {
+                    report.push_str(&format!("\n    at {}: {counts}", checkpoint.label));
+                }
+            }
+        }
+        panic!("{report}");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::owner::Owner;
+    use crate::transfer::transfer;
+
+    fn counts(strong: usize, weak: usize) -> RefCounts {
+        RefCounts { strong, weak }
+    }
+
+    #[test]
+    fn creation_and_drop_free_everything() {
+        let mut tracker = LeakTracker::new();
+        let owner = Owner::new("owner");
+        let gadget = owner.add_gadget(1);
+        tracker.track_node(&owner);
+        tracker.track_node(&gadget);
+
+        // The gadget keeps its owner alive; the owner only lists it weakly.
+        // The weak count covers its entry in the list and in the id index.
+        tracker.assert_counts("Owner(owner)", counts(2, 0));
+        tracker.assert_counts("Gadget(1)", counts(1, 2));
+
+        drop(gadget);
+        tracker.assert_counts("Owner(owner)", counts(1, 0));
+        tracker.assert_counts("Gadget(1)", counts(0, 0));
+        drop(owner);
+        tracker.assert_all_freed();
+    }
+
+    #[test]
+    fn transfer_moves_the_strong_back_reference() {
+        let mut tracker = LeakTracker::new();
+        let old_owner = Owner::new("old");
+        let new_owner = Owner::new("new");
+        let gadget = old_owner.add_gadget(1);
+        tracker.track_node(&old_owner);
+        tracker.track_node(&new_owner);
+        tracker.track_node(&gadget);
+        tracker.checkpoint("before transfer");
+
+        transfer(&gadget, &new_owner);
+        tracker.checkpoint("after transfer");
+        tracker.assert_counts("Owner(old)", counts(1, 0));
+        tracker.assert_counts("Owner(new)", counts(2, 0));
+
+        drop(old_owner);
+        drop(new_owner);
+        // The gadget still holds its new owner.
+        assert_eq!(tracker.leaked(), ["Owner(new)", "Gadget(1)"]);
+        drop(gadget);
+        tracker.assert_all_freed();
+        assert_eq!(tracker.checkpoints().len(), 2);
+    }
+
+    #[test]
+    fn pruning_leaves_nothing_behind() {
+        let mut tracker = LeakTracker::new();
+        let owner = Owner::new("owner");
+        let gadget = owner.add_gadget(1);
+        tracker.track_node(&owner);
+        tracker.track_node(&gadget);
+
+        // Dropping the gadget while the list is borrowed leaves a dangling entry.
+        let list = owner.gadget_list().try_borrow().unwrap();
+        drop(gadget);
+        drop(list);
+        assert_eq!(owner.dangling_count(), 1);
+        tracker.assert_counts("Gadget(1)", counts(0, 0));
+
+        assert_eq!(owner.prune(), 1);
+        assert_eq!(owner.dangling_count(), 0);
+        drop(owner);
+        tracker.assert_all_freed();
+    }
+
+    #[test]
+    fn owner_dropped_first_is_freed_with_its_last_gadget() {
+        let mut tracker = LeakTracker::new();
+        let parent = Owner::new("parent");
+        let child = Owner::new("child");
+        parent.add_child(&child);
+        let gadget = child.add_gadget(1);
+        tracker.track_node(&parent);
+        tracker.track_node(&child);
+        tracker.track_node(&gadget);
+
+        drop(child);
+        drop(parent);
+        // The parent went with its last handle; the gadget still holds the child.
+        assert_eq!(tracker.leaked(), ["Owner(child)", "Gadget(1)"]);
+        tracker.assert_counts("Owner(child)", counts(1, 0));
+
+        drop(gadget);
+        tracker.assert_all_freed();
+    }
+
+    #[test]
+    fn gadgets_with_the_same_id_get_distinct_labels() {
+        let mut tracker = LeakTracker::new();
+        let first = Owner::new("first");
+        let second = Owner::new("second");
+        let kept = first.add_gadget(1);
+        let dropped = second.add_gadget(1);
+        assert_eq!(tracker.track_node(&kept), "Gadget(1)");
+        assert_eq!(tracker.track_node(&dropped), "Gadget(1) #2");
+
+        drop(dropped);
+        tracker.assert_counts("Gadget(1)", counts(1, 2));
+        tracker.assert_counts("Gadget(1) #2", counts(0, 0));
+        assert_eq!(tracker.leaked(), ["Gadget(1)"]);
+    }
+
+    #[test]
+    #[should_panic(expected = "gadget is already tracked")]
+    fn duplicate_labels_are_rejected() {
+        let mut tracker = LeakTracker::new();
+        let owner = Owner::new("owner");
+        let first = owner.add_gadget(1);
+        let second = owner.add_gadget(2);
+        tracker.track("gadget", &first);
+        tracker.track("gadget", &second);
+    }
+}
@@ -0,0 +1,36 @@
+// An Rc pointer from Owner to Gadget introduces a cycle.
+// This means that their reference counts can never reach 0,
+// and the allocation will never be destroyed: a memory leak.
+//
+// Gadgets therefore hold a strong `Rc<Owner>` back-reference,
+// while an Owner only keeps `Weak<Gadget>` pointers to its gadgets.
+//
+// Both are generic over a payload (`Owner<O, G>` / `Gadget<O, G>`),
+// so the same ownership mechanics work for accounts and sessions,
+// projects and tasks, or any other parent/child pair.
+// The payloads default to `()`, which gives back the plain Owner/Gadget pair.
+
+pub mod arena;
+mod backend;
+pub mod csv;
+pub mod cycles;
+mod diagnostics;
+mod error;
+mod gadget;
+mod gadget_list;
+pub mod json;
+pub mod leak;
+mod owner;
+mod registry;
+pub mod snapshot;
+pub mod sync;
+mod tracked_cell;
+mod transfer;
+
+pub use backend::Backend;
+pub use diagnostics::{BorrowState, DanglingEntry, Diagnostics, GadgetCounts};
+pub use error::Error;
+pub use gadget::{Gadget, GadgetStatus};
+pub use owner::{Event, NameChange, Owner, PrunePolicy, Subscription};
+pub use registry::{GadgetHandle, OwnerId, Registry};
+pub use transfer::{transfer, try_transfer};
@@ -1,75 +1,61 @@
-use std::cell::RefCell;
-use std::rc::{Rc, Weak};
-
-// An Rc pointer from Owner to Gadget introduces a cycle.
-// This means that their reference counts can never reach 0,
-// and the allocation will never be destroyed: a memory leak.
-
-struct Owner {
-    name: String,
-    // Weak RC gets around the memory leak problem.
-    // a Weak reference does not count towards ownership,
-    // it will not prevent the value stored in the allocation from being dropped,
-    // and Weak itself makes no guarantees about the value still being present.
-    // Thus it may return None when upgraded.
-    // Note however that a Weak reference does prevent the allocation itself
-    // (the backing store) from being deallocated.
-
-    // Weak pointer is useful for keeping a temporary reference to the allocation managed by Rc without preventing its inner value from being dropped.
-    // It is also used to prevent circular references between Rc pointers,
-    // since mutual owning references would never allow either Rc to be dropped.
-    gadgets: RefCell<Vec<Weak<Gadget>>>,
-}
-
-struct Gadget {
-    id: i32,
-    owner: Rc<Owner>,
-}
+use rc_test2::leak::LeakTracker;
+use rc_test2::{transfer, Owner};
 
 fn main() {
-    let gadget_owner: Rc<Owner> = Rc::new(Owner {
-        name: "Gadget Man".to_string(),
-        // Rc enforces memory safety by only giving out shared references to the value it wraps,
-        // and these don’t allow direct mutation.
-        // We need to wrap the part of the value we wish to mutate in a RefCell.
-        // which provides interior mutability:
-        // a method to achieve mutability through a shared reference.
-        gadgets: RefCell::new(vec![]),
-    });
-
-    let gadget1 = Rc::new(Gadget {
-        id: 1,
-        owner: Rc::clone(&gadget_owner),
-    });
-
-    let gadget2 = Rc::new(Gadget {
-        id: 2,
-        owner: Rc::clone(&gadget_owner),
-    });
+    let gadget_owner = Owner::new("Gadget Man");
 
-    {
-        // Without RefCell the RC items inside Vec cannot be mutated.
-        // You cannot generally obtain a mutable reference to something inside an Rc.
-        // If you need mutability, put a Cell or RefCell inside the Rc;
-        let mut gadgets = gadget_owner.gadgets.borrow_mut();
-        gadgets.push(Rc::downgrade(&gadget1));
-        gadgets.push(Rc::downgrade(&gadget2));
+    // Keep the strong handles alive: the owner only holds `Weak` pointers.
+    // Ids are handed out by the owner, so they are unique among its gadgets.
+    let gadget1 = gadget_owner.add_next_gadget();
+    let gadget2 = gadget_owner.add_next_gadget();
 
-        // `RefCell` dynamic borrow ends here.
+    // Registering an id that is already taken is rejected.
+    if let Err(err) = gadget_owner.try_add_gadget(2) {
+        println!("Cannot add gadget: {err}");
     }
 
-    for gadget in gadget_owner.gadgets.borrow().iter() {
-        // `gadget` is a `Weak<Gadget>.
-        // Upgrade the Weak RC before accessing containing data.
-        // Since `Weak` pointers can't guarantee the allocation still exists,
-        // we need to call `upgrade`, which returns an `Option<Rc<Gadget>>.
+    // Dropping the last strong handle destroys the gadget,
+    // which removes its `Weak` from the owner's list on the way out.
+    drop(gadget1);
 
-        // In this case we know the allocation still exists,
-        // so `unwrap` the `Option` is acceptable.
-        // In a more complicated program,
-        // graceful error handling might be required for a `None` result.
+    // `live_gadgets` upgrades every `Weak<Gadget>` and skips those
+    // returning `None`, so there is no `unwrap` that could panic here.
+    for gadget in gadget_owner.live_gadgets() {
+        println!("Gadget {} owned by {}", gadget.id(), gadget.owner().name());
+    }
 
-        let gadget = gadget.upgrade().unwrap();
-        println!("Gadget {} owned by {}", gadget.id, gadget.owner.name);
+    // Lookups by id go through the owner's index instead of a linear scan.
+    if let Some(gadget) = gadget_owner.gadget(2) {
+        println!("Found gadget {} by id", gadget.id());
     }
+
+    println!(
+        "{} dangling gadget reference(s)",
+        gadget_owner.dangling_count()
+    );
+
+    // Handing a gadget over moves its `Weak` to the new owner's list
+    // and repoints the gadget's `Rc<Owner>` back-reference.
+    let new_owner = Owner::new("Gadget Woman");
+    transfer(&gadget2, &new_owner);
+    println!(
+        "Gadget {} now owned by {} ({} left with {})",
+        gadget2.id(),
+        gadget2.owner().name(),
+        gadget_owner.live_gadgets().count(),
+        gadget_owner.name()
+    );
+
+    // With only `Weak` pointers from owners to gadgets there is no cycle,
+    // so everything is freed once the last strong handles go away.
+    let mut tracker = LeakTracker::new();
+    tracker.track_node(&gadget_owner);
+    tracker.track_node(&new_owner);
+    tracker.track_node(&gadget2);
+    tracker.checkpoint("end of demo");
+    drop(gadget2);
+    drop(new_owner);
+    drop(gadget_owner);
+    tracker.assert_all_freed();
+    println!("All owners and gadgets freed");
 }
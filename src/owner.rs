@@ -0,0 +1,418 @@
+use std::cell::Cell;
+use std::collections::VecDeque;
+use std::rc::{Rc, Weak};
+
+use crate::error::Error;
+use crate::gadget::Gadget;
+use crate::gadget_list::GadgetList;
+use crate::tracked_cell::TrackedRefCell;
+
+mod events;
+mod hierarchy;
+mod rename;
+
+use events::Listener;
+pub use events::{Event, Subscription};
+pub use rename::NameChange;
+
+pub struct Owner<O = (), G = ()> {
+    // Behind a RefCell so an owner shared through `Rc` can still be renamed.
+    name: TrackedRefCell<String>,
+    // Bounded by `name_history_limit`; empty while the limit is 0.
+    name_history: TrackedRefCell<VecDeque<NameChange>>,
+    name_history_limit: Cell<usize>,
+    data: O,
+    // Weak RC gets around the memory leak problem.
+    // a Weak reference does not count towards ownership,
+    // it will not prevent the value stored in the allocation from being dropped,
+    // and Weak itself makes no guarantees about the value still being present.
+    // Thus it may return None when upgraded.
+    // Note however that a Weak reference does prevent the allocation itself
+    // (the backing store) from being deallocated.
+
+    // Weak pointer is useful for keeping a temporary reference to the allocation managed by Rc without preventing its inner value from being dropped.
+    // It is also used to prevent circular references between Rc pointers,
+    // since mutual owning references would never allow either Rc to be dropped.
+    //
+    // The list is also indexed by `Gadget::id`, see `GadgetList`.
+    gadgets: TrackedRefCell<GadgetList<Weak<Gadget<O, G>>>>,
+    prune_policy: Cell<PrunePolicy>,
+    // How many entries of `gadgets` are dangling: only `deregister` leaves one
+    // behind, when the list is borrowed while a gadget is dropped.
+    // It cannot record that in the list itself, hence the separate `Cell`;
+    // `prune` removes them all and resets it.
+    dangling: Cell<usize>,
+    // Lower bound for the next id handed out by `Owner::next_id`.
+    next_id: Cell<i32>,
+    // Owners nest into a tree (organisation, teams, people).
+    // A parent keeps its children alive through strong `Rc`s,
+    // while a child only points back with a `Weak`,
+    // so the two directions never form a cycle.
+    parent: TrackedRefCell<Weak<Owner<O, G>>>,
+    children: TrackedRefCell<Vec<Rc<Owner<O, G>>>>,
+    // Held weakly, see `Subscription`.
+    listeners: TrackedRefCell<Vec<Weak<Listener<O, G>>>>,
+}
+
+/// Controls when an [`Owner`] compacts dangling `Weak` entries on its own.
+///
+/// Dead `Weak` pointers keep their backing allocation alive,
+/// so a list that only grows leaks memory under gadget churn.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum PrunePolicy {
+    /// Dangling entries are only removed by an explicit [`Owner::prune`].
+    #[default]
+    Manual,
+    /// Prune on insert once the fraction of dead entries reaches the threshold.
+    DeadRatio(f64),
+}
+
+impl PrunePolicy {
+    pub(crate) fn should_prune(self, dead: usize, total: usize) -> bool {
+        match self {
+            PrunePolicy::Manual => false,
+            PrunePolicy::DeadRatio(threshold) => {
+                dead > 0 && dead as f64 / total as f64 >= threshold
+            }
+        }
+    }
+}
+
+impl Owner {
+    /// Creates a new owner without any gadgets.
+    pub fn new(name: impl Into<String>) -> Rc<Owner> {
+        Owner::with_data(name, ())
+    }
+}
+
+impl<O, G> Owner<O, G> {
+    /// Creates a new owner carrying `data` as its payload.
+    pub fn with_data(name: impl Into<String>, data: O) -> Rc<Self> {
+        Rc::new(Owner {
+            name: TrackedRefCell::new("owner name", name.into()),
+            name_history: TrackedRefCell::new("name history", VecDeque::new()),
+            name_history_limit: Cell::new(0),
+            data,
+            // Rc enforces memory safety by only giving out shared references to the value it wraps,
+            // and these don’t allow direct mutation.
+            // We need to wrap the part of the value we wish to mutate in a RefCell.
+            // which provides interior mutability:
+            // a method to achieve mutability through a shared reference.
+            gadgets: TrackedRefCell::new("gadget list", GadgetList::new()),
+            prune_policy: Cell::new(PrunePolicy::default()),
+            dangling: Cell::new(0),
+            next_id: Cell::new(1),
+            parent: TrackedRefCell::new("parent", Weak::new()),
+            children: TrackedRefCell::new("children", vec![]),
+            listeners: TrackedRefCell::new("listeners", vec![]),
+        })
+    }
+
+    pub fn data(&self) -> &O {
+        &self.data
+    }
+
+    /// Creates a gadget carrying `data`, owned by this owner, and registers it in one step.
+    ///
+    /// The gadget receives a strong back-reference to the owner,
+    /// and the owner records a `Weak` pointer to the gadget,
+    /// so the two sides of the relationship can never drift apart.
+    ///
+    /// Fails with [`Error::DuplicateId`] if a live gadget with the same id
+    /// is already registered on this owner,
+    /// and with [`Error::AlreadyBorrowed`] if the gadget list is in use.
+    #[track_caller]
+    pub fn try_add_gadget_with(
+        self: &Rc<Self>,
+        id: i32,
+        data: G,
+    ) -> Result<Rc<Gadget<O, G>>, Error> {
+        // Without RefCell the RC items inside Vec cannot be mutated.
+        // You cannot generally obtain a mutable reference to something inside an Rc.
+        // If you need mutability, put a Cell or RefCell inside the Rc;
+        let mut gadgets = self.gadgets.try_borrow_mut()?;
+        if gadgets
+            .get(id)
+            .is_some_and(|gadget| gadget.strong_count() > 0)
+        {
+            return Err(Error::DuplicateId(id));
+        }
+        let gadget = Rc::new(Gadget::new(id, data, Rc::clone(self)));
+
+        if self
+            .prune_policy
+            .get()
+            .should_prune(self.dangling.get(), gadgets.len())
+        {
+            gadgets.prune();
+            self.dangling.set(0);
+        }
+        gadgets.push(id, Rc::downgrade(&gadget));
+
+        // `RefCell` dynamic borrow ends here.
+        // Listeners are only notified afterwards, so they can read the gadget list.
+        drop(gadgets);
+        self.emit(Event::GadgetAdded { id });
+        Ok(gadget)
+    }
+
+    /// Like [`Owner::try_add_gadget_with`], but panics on failure.
+    #[track_caller]
+    pub fn add_gadget_with(self: &Rc<Self>, id: i32, data: G) -> Rc<Gadget<O, G>> {
+        self.try_add_gadget_with(id, data).unwrap_or_else(fail)
+    }
+
+    /// Hands out the lowest id, at or above the previously allocated one,
+    /// that no live gadget of this owner is using.
+    #[track_caller]
+    pub fn try_next_id(&self) -> Result<i32, Error> {
+        let gadgets = self.gadgets.try_borrow()?;
+        let mut id = self.next_id.get();
+        while gadgets
+            .get(id)
+            .is_some_and(|gadget| gadget.strong_count() > 0)
+        {
+            id += 1;
+        }
+        self.next_id.set(id + 1);
+        Ok(id)
+    }
+
+    /// Like [`Owner::try_next_id`], but panics on failure.
+    #[track_caller]
+    pub fn next_id(&self) -> i32 {
+        self.try_next_id().unwrap_or_else(fail)
+    }
+
+    /// Creates and registers a gadget under an id picked by [`Owner::try_next_id`].
+    #[track_caller]
+    pub fn try_add_next_gadget_with(self: &Rc<Self>, data: G) -> Result<Rc<Gadget<O, G>>, Error> {
+        let id = self.try_next_id()?;
+        self.try_add_gadget_with(id, data)
+    }
+
+    /// Like [`Owner::try_add_next_gadget_with`], but panics on failure.
+    #[track_caller]
+    pub fn add_next_gadget_with(self: &Rc<Self>, data: G) -> Rc<Gadget<O, G>> {
+        self.try_add_next_gadget_with(data).unwrap_or_else(fail)
+    }
+
+    /// Returns a snapshot of the `Weak` pointers to this owner's gadgets.
+    ///
+    /// The snapshot is detached from the internal `RefCell`,
+    /// so holding on to it never blocks later registrations.
+    #[track_caller]
+    pub fn try_gadgets(&self) -> Result<Vec<Weak<Gadget<O, G>>>, Error> {
+        Ok(self.gadgets.try_borrow()?.entries().cloned().collect())
+    }
+
+    /// Like [`Owner::try_gadgets`], but panics on failure.
+    #[track_caller]
+    pub fn gadgets(&self) -> Vec<Weak<Gadget<O, G>>> {
+        self.try_gadgets().unwrap_or_else(fail)
+    }
+
+    /// Looks up a live gadget by its id.
+    ///
+    /// Fails with [`Error::UnknownId`] if no gadget was registered under `id`,
+    /// and with [`Error::DanglingGadget`] if it has been dropped.
+    #[track_caller]
+    pub fn try_gadget(&self, id: i32) -> Result<Rc<Gadget<O, G>>, Error> {
+        let gadgets = self.gadgets.try_borrow()?;
+        let gadget = gadgets.get(id).ok_or(Error::UnknownId(id))?;
+        gadget.upgrade().ok_or(Error::DanglingGadget(id))
+    }
+
+    /// Like [`Owner::try_gadget`], but returns `None` for unknown or dropped gadgets
+    /// and panics if the gadget list is in use.
+    #[track_caller]
+    pub fn gadget(&self, id: i32) -> Option<Rc<Gadget<O, G>>> {
+        match self.try_gadget(id) {
+            Ok(gadget) => Some(gadget),
+            Err(Error::UnknownId(_) | Error::DanglingGadget(_)) => None,
+            Err(err) => fail(err),
+        }
+    }
+
+    /// Iterates over the gadgets that are still alive.
+    ///
+    /// Entries whose `Weak` pointer can no longer be upgraded are skipped,
+    /// so dropping a gadget is a normal event rather than a crash.
+    #[track_caller]
+    pub fn try_live_gadgets(&self) -> Result<impl Iterator<Item = Rc<Gadget<O, G>>>, Error> {
+        Ok(self
+            .try_gadgets()?
+            .into_iter()
+            .filter_map(|gadget| gadget.upgrade()))
+    }
+
+    /// Like [`Owner::try_live_gadgets`], but panics on failure.
+    #[track_caller]
+    pub fn live_gadgets(&self) -> impl Iterator<Item = Rc<Gadget<O, G>>> {
+        self.try_live_gadgets().unwrap_or_else(fail)
+    }
+
+    /// Returns how many entries in the gadget list point to dropped gadgets.
+    #[track_caller]
+    pub fn try_dangling_count(&self) -> Result<usize, Error> {
+        Ok(self.gadgets.try_borrow()?.dangling_count())
+    }
+
+    /// Like [`Owner::try_dangling_count`], but panics on failure.
+    #[track_caller]
+    pub fn dangling_count(&self) -> usize {
+        self.try_dangling_count().unwrap_or_else(fail)
+    }
+
+    pub(crate) fn gadget_list(&self) -> &TrackedRefCell<GadgetList<Weak<Gadget<O, G>>>> {
+        &self.gadgets
+    }
+
+    /// Removes the entry pointing at `gadget`, called while that gadget is dropped.
+    ///
+    /// If the gadget list is currently borrowed the entry is left behind
+    /// as a dangling `Weak`, to be cleaned up later by [`Owner::prune`].
+    pub(crate) fn deregister(&self, gadget: &Gadget<O, G>) {
+        // `try_borrow_mut` instead of `borrow_mut`:
+        // panicking inside `Drop` while already unwinding would abort the process.
+        match self.gadgets.try_borrow_mut() {
+            Ok(mut gadgets) => gadgets.remove(gadget.id(), gadget),
+            Err(_) => self.dangling.set(self.dangling.get() + 1),
+        }
+    }
+
+    /// Removes every dangling entry from the gadget list,
+    /// releasing the backing allocations they kept alive.
+    ///
+    /// Returns the number of entries removed.
+    #[track_caller]
+    pub fn try_prune(&self) -> Result<usize, Error> {
+        let removed = self.gadgets.try_borrow_mut()?.prune();
+        self.dangling.set(0);
+        Ok(removed)
+    }
+
+    /// Like [`Owner::try_prune`], but panics on failure.
+    #[track_caller]
+    pub fn prune(&self) -> usize {
+        self.try_prune().unwrap_or_else(fail)
+    }
+
+    pub fn prune_policy(&self) -> PrunePolicy {
+        self.prune_policy.get()
+    }
+
+    /// Sets the policy used to compact the gadget list on insert.
+    pub fn set_prune_policy(&self, policy: PrunePolicy) {
+        self.prune_policy.set(policy);
+    }
+}
+
+impl<O, G: Default> Owner<O, G> {
+    /// Creates a gadget with a default payload, see [`Owner::add_gadget_with`].
+    #[track_caller]
+    pub fn add_gadget(self: &Rc<Self>, id: i32) -> Rc<Gadget<O, G>> {
+        self.add_gadget_with(id, G::default())
+    }
+
+    /// Creates a gadget with a default payload, see [`Owner::try_add_gadget_with`].
+    #[track_caller]
+    pub fn try_add_gadget(self: &Rc<Self>, id: i32) -> Result<Rc<Gadget<O, G>>, Error> {
+        self.try_add_gadget_with(id, G::default())
+    }
+
+    /// Creates a gadget with a default payload, see [`Owner::add_next_gadget_with`].
+    #[track_caller]
+    pub fn add_next_gadget(self: &Rc<Self>) -> Rc<Gadget<O, G>> {
+        self.add_next_gadget_with(G::default())
+    }
+
+    /// Creates a gadget with a default payload, see [`Owner::try_add_next_gadget_with`].
+    #[track_caller]
+    pub fn try_add_next_gadget(self: &Rc<Self>) -> Result<Rc<Gadget<O, G>>, Error> {
+        self.try_add_next_gadget_with(G::default())
+    }
+}
+
+// The panicking counterpart shared by every non-`try_` operation.
+pub(crate) fn fail<T>(err: Error) -> T {
+    panic!("{err}")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn manual_policy_never_prunes() {
+        assert!(!PrunePolicy::Manual.should_prune(100, 100));
+    }
+
+    #[test]
+    fn dead_ratio_policy_prunes_at_threshold() {
+        let policy = PrunePolicy::DeadRatio(0.5);
+        assert!(!policy.should_prune(0, 10));
+        assert!(!policy.should_prune(4, 10));
+        assert!(policy.should_prune(5, 10));
+    }
+
+    // Drops `ids` while the gadget list is borrowed, leaving dangling entries behind.
+    fn drop_while_borrowed(owner: &Rc<Owner>, gadgets: &mut Vec<Rc<Gadget>>, ids: &[i32]) {
+        let list = owner.gadgets.try_borrow().unwrap();
+        gadgets.retain(|gadget| !ids.contains(&gadget.id()));
+        drop(list);
+    }
+
+    #[test]
+    fn dead_ratio_policy_compacts_on_insert() {
+        let owner = Owner::new("owner");
+        owner.set_prune_policy(PrunePolicy::DeadRatio(0.5));
+        let mut gadgets: Vec<_> = (1..=4).map(|id| owner.add_gadget(id)).collect();
+
+        drop_while_borrowed(&owner, &mut gadgets, &[1]);
+        gadgets.push(owner.add_gadget(5));
+        // 1 dead entry out of 4 is below the threshold.
+        assert_eq!(owner.gadgets().len(), 5);
+        assert_eq!(owner.dangling_count(), 1);
+
+        drop_while_borrowed(&owner, &mut gadgets, &[2, 3]);
+        gadgets.push(owner.add_gadget(6));
+        // 3 out of 5 reach it: the list is compacted before the new entry goes in.
+        assert_eq!(owner.dangling_count(), 0);
+        let ids: Vec<_> = owner.live_gadgets().map(|gadget| gadget.id()).collect();
+        assert_eq!(ids, [4, 5, 6]);
+        assert_eq!(owner.gadgets().len(), 3);
+
+        // Nothing is dangling any more, so the next insert does not prune.
+        drop_while_borrowed(&owner, &mut gadgets, &[4]);
+        gadgets.push(owner.add_gadget(7));
+        assert_eq!(owner.gadgets().len(), 4);
+        assert_eq!(owner.dangling_count(), 1);
+    }
+
+    #[test]
+    fn manual_policy_leaves_dangling_entries_until_pruned() {
+        let owner = Owner::new("owner");
+        let mut gadgets: Vec<_> = (1..=4).map(|id| owner.add_gadget(id)).collect();
+
+        drop_while_borrowed(&owner, &mut gadgets, &[1, 2, 3]);
+        gadgets.push(owner.add_gadget(5));
+        assert_eq!(owner.dangling_count(), 3);
+        assert_eq!(owner.prune(), 3);
+        assert_eq!(owner.gadgets().len(), 2);
+    }
+
+    #[test]
+    fn dropping_gadgets_keeps_the_others_in_order() {
+        let owner = Owner::new("owner");
+        let mut gadgets: Vec<_> = (1..=10).map(|id| owner.add_gadget(id)).collect();
+        gadgets.retain(|gadget| gadget.id() % 3 != 0);
+
+        let ids: Vec<_> = owner.live_gadgets().map(|gadget| gadget.id()).collect();
+        assert_eq!(ids, [1, 2, 4, 5, 7, 8, 10]);
+        assert_eq!(owner.gadgets().len(), 7);
+        assert_eq!(owner.dangling_count(), 0);
+        assert!(owner.gadget(3).is_none());
+        assert_eq!(owner.gadget(4).map(|gadget| gadget.id()), Some(4));
+    }
+}
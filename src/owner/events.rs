@@ -0,0 +1,210 @@
+use std::rc::{Rc, Weak};
+
+use super::{fail, Owner};
+use crate::error::Error;
+
+/// A change in the life of an owner or one of its gadgets.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Event {
+    /// A gadget was registered on the owner, as primary owner or co-owner.
+    GadgetAdded { id: i32 },
+    /// A gadget of the owner was dropped.
+    GadgetDropped { id: i32 },
+    /// A gadget stopped being co-owned by the owner but stays alive,
+    /// see [`Gadget::remove_co_owner`](crate::Gadget::remove_co_owner).
+    GadgetRemoved { id: i32 },
+    /// A gadget moved from one owner to another; both of them are notified.
+    GadgetTransferred { id: i32, from: String, to: String },
+    /// The owner's name changed.
+    OwnerRenamed { old: String, new: String },
+}
+
+pub(crate) type Listener<O, G> = dyn Fn(&Owner<O, G>, &Event);
+
+/// Keeps a listener registered with [`Owner::subscribe`] alive.
+///
+/// The owner only holds a `Weak` pointer to the listener,
+/// so a listener capturing an `Rc<Owner>` does not keep that owner alive
+/// through a cycle. Dropping the subscription unsubscribes.
+#[must_use = "the listener is unsubscribed as soon as the subscription is dropped"]
+pub struct Subscription<O = (), G = ()> {
+    _listener: Rc<Listener<O, G>>,
+}
+
+impl<O, G> Owner<O, G> {
+    /// Calls `listener` for every event of this owner, as long as the
+    /// returned [`Subscription`] is alive.
+    ///
+    /// Listeners run after the owner has released its own borrows,
+    /// so they are free to read (or even modify) its gadgets.
+    #[track_caller]
+    pub fn try_subscribe(
+        &self,
+        listener: impl Fn(&Owner<O, G>, &Event) + 'static,
+    ) -> Result<Subscription<O, G>, Error> {
+        let mut listeners = self.listeners.try_borrow_mut()?;
+        let listener: Rc<Listener<O, G>> = Rc::new(listener);
+        listeners.retain(|listener| listener.strong_count() > 0);
+        listeners.push(Rc::downgrade(&listener));
+        Ok(Subscription {
+            _listener: listener,
+        })
+    }
+
+    /// Like [`Owner::try_subscribe`], but panics on failure.
+    #[track_caller]
+    pub fn subscribe(
+        &self,
+        listener: impl Fn(&Owner<O, G>, &Event) + 'static,
+    ) -> Subscription<O, G> {
+        self.try_subscribe(listener).unwrap_or_else(fail)
+    }
+
+    pub(crate) fn emit(&self, event: Event) {
+        // Upgrade into a snapshot first: a listener may subscribe
+        // or drop its subscription while the event is being delivered.
+        let listeners: Vec<Rc<Listener<O, G>>> = self
+            .listeners
+            .borrow()
+            .iter()
+            .filter_map(Weak::upgrade)
+            .collect();
+        for listener in listeners {
+            listener(self, &event);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::RefCell;
+
+    use super::*;
+    use crate::transfer::transfer;
+
+    type Log = Rc<RefCell<Vec<(String, Event)>>>;
+
+    // Records every event of `owner` into `log`, tagged with the owner's name.
+    fn record(owner: &Owner, log: &Log) -> Subscription {
+        let log = Rc::clone(log);
+        owner.subscribe(move |owner, event| log.borrow_mut().push((owner.name(), event.clone())))
+    }
+
+    fn entry(owner: &str, event: Event) -> (String, Event) {
+        (owner.to_string(), event)
+    }
+
+    #[test]
+    fn each_event_fires_once_per_affected_owner() {
+        let log = Log::default();
+        let ann = Owner::new("Ann");
+        let bob = Owner::new("Bob");
+        let _ann_events = record(&ann, &log);
+        let _bob_events = record(&bob, &log);
+
+        let gadget = ann.add_gadget(1);
+        gadget.add_co_owner(&bob);
+        gadget.remove_co_owner(&bob);
+        transfer(&gadget, &bob);
+        ann.rename("Anna");
+        gadget.add_co_owner(&ann);
+        drop(gadget);
+
+        let transferred = Event::GadgetTransferred {
+            id: 1,
+            from: "Ann".to_string(),
+            to: "Bob".to_string(),
+        };
+        assert_eq!(
+            *log.borrow(),
+            [
+                entry("Ann", Event::GadgetAdded { id: 1 }),
+                entry("Bob", Event::GadgetAdded { id: 1 }),
+                entry("Bob", Event::GadgetRemoved { id: 1 }),
+                entry("Ann", transferred.clone()),
+                entry("Bob", transferred),
+                entry(
+                    "Anna",
+                    Event::OwnerRenamed {
+                        old: "Ann".to_string(),
+                        new: "Anna".to_string(),
+                    }
+                ),
+                entry("Anna", Event::GadgetAdded { id: 1 }),
+                entry("Bob", Event::GadgetDropped { id: 1 }),
+                entry("Anna", Event::GadgetDropped { id: 1 }),
+            ]
+        );
+    }
+
+    #[test]
+    fn listeners_can_read_the_gadget_list() {
+        let owner = Owner::new("owner");
+        let seen = Rc::new(RefCell::new(vec![]));
+        let _subscription = owner.subscribe({
+            let seen = Rc::clone(&seen);
+            move |owner, event| {
+                let ids: Vec<i32> = owner.live_gadgets().map(|gadget| gadget.id()).collect();
+                let found = match event {
+                    Event::GadgetAdded { id } => owner.gadget(*id).is_some(),
+                    _ => false,
+                };
+                seen.borrow_mut().push((ids, found, owner.dangling_count()));
+            }
+        });
+
+        let first = owner.add_gadget(1);
+        let second = owner.add_gadget(2);
+        drop(first);
+
+        assert_eq!(
+            *seen.borrow(),
+            [
+                (vec![1], true, 0),
+                (vec![1, 2], true, 0),
+                // The dropped gadget has already left the list.
+                (vec![2], false, 0),
+            ]
+        );
+        drop(second);
+    }
+
+    #[test]
+    fn dropping_the_subscription_stops_delivery() {
+        let log = Log::default();
+        let owner = Owner::new("owner");
+        let subscription = record(&owner, &log);
+        let _first = owner.add_gadget(1);
+
+        drop(subscription);
+        let _second = owner.add_gadget(2);
+
+        assert_eq!(
+            *log.borrow(),
+            [entry("owner", Event::GadgetAdded { id: 1 })]
+        );
+        assert!(owner
+            .listeners
+            .borrow()
+            .iter()
+            .all(|listener| listener.strong_count() == 0));
+    }
+
+    #[test]
+    fn listeners_do_not_keep_their_owner_alive() {
+        let owner = Owner::new("owner");
+        let weak = Rc::downgrade(&owner);
+        let subscription = owner.subscribe({
+            // A listener holding its own owner: only the subscription keeps it alive.
+            let owner = Rc::clone(&owner);
+            move |_, _| {
+                owner.name();
+            }
+        });
+
+        drop(owner);
+        assert!(weak.upgrade().is_some());
+        drop(subscription);
+        assert!(weak.upgrade().is_none());
+    }
+}
@@ -0,0 +1,218 @@
+use std::collections::HashSet;
+use std::rc::{Rc, Weak};
+
+use super::{fail, Owner};
+use crate::error::Error;
+use crate::gadget::Gadget;
+
+impl<O, G> Owner<O, G> {
+    /// Returns the owner this one is nested under, if any.
+    #[track_caller]
+    pub fn parent(&self) -> Option<Rc<Owner<O, G>>> {
+        self.parent.borrow().upgrade()
+    }
+
+    /// Returns the owners nested directly under this one.
+    #[track_caller]
+    pub fn try_children(&self) -> Result<Vec<Rc<Owner<O, G>>>, Error> {
+        Ok(self.children.try_borrow()?.clone())
+    }
+
+    /// Like [`Owner::try_children`], but panics on failure.
+    #[track_caller]
+    pub fn children(&self) -> Vec<Rc<Owner<O, G>>> {
+        self.try_children().unwrap_or_else(fail)
+    }
+
+    /// Nests `child` under this owner, detaching it from its previous parent.
+    ///
+    /// Fails with [`Error::HierarchyCycle`] if `child` is this owner
+    /// or one of its ancestors: the parent would then keep itself alive.
+    #[track_caller]
+    pub fn try_add_child(self: &Rc<Self>, child: &Rc<Owner<O, G>>) -> Result<(), Error> {
+        let mut ancestor = Some(Rc::clone(self));
+        while let Some(owner) = ancestor {
+            if Rc::ptr_eq(&owner, child) {
+                return Err(Error::HierarchyCycle);
+            }
+            ancestor = owner.parent();
+        }
+
+        let mut children = self.children.try_borrow_mut()?;
+        let mut parent = child.parent.try_borrow_mut()?;
+        match parent.upgrade() {
+            Some(previous) if Rc::ptr_eq(&previous, self) => return Ok(()),
+            Some(previous) => previous
+                .children
+                .try_borrow_mut()?
+                .retain(|sibling| !Rc::ptr_eq(sibling, child)),
+            None => {}
+        }
+        children.push(Rc::clone(child));
+        *parent = Rc::downgrade(self);
+        Ok(())
+    }
+
+    /// Like [`Owner::try_add_child`], but panics on failure.
+    #[track_caller]
+    pub fn add_child(self: &Rc<Self>, child: &Rc<Owner<O, G>>) {
+        self.try_add_child(child).unwrap_or_else(fail)
+    }
+
+    /// Detaches `child` from this owner, leaving it without a parent.
+    ///
+    /// Fails with [`Error::NotAChild`] if `child` is not nested directly under this owner.
+    #[track_caller]
+    pub fn try_remove_child(&self, child: &Rc<Owner<O, G>>) -> Result<(), Error> {
+        let mut children = self.children.try_borrow_mut()?;
+        let position = children
+            .iter()
+            .position(|candidate| Rc::ptr_eq(candidate, child))
+            .ok_or(Error::NotAChild)?;
+        *child.parent.try_borrow_mut()? = Weak::new();
+        // `child` is still borrowed by the caller, so this cannot free it.
+        children.remove(position);
+        Ok(())
+    }
+
+    /// Like [`Owner::try_remove_child`], but panics on failure.
+    #[track_caller]
+    pub fn remove_child(&self, child: &Rc<Owner<O, G>>) {
+        self.try_remove_child(child).unwrap_or_else(fail)
+    }
+
+    /// Collects the live gadgets of this owner and of every owner nested under it.
+    ///
+    /// The walk only follows the strong parent-to-child links,
+    /// so it visits each owner once; a gadget co-owned by several of them
+    /// is reported once as well.
+    #[track_caller]
+    pub fn try_all_gadgets(&self) -> Result<Vec<Rc<Gadget<O, G>>>, Error> {
+        let mut seen = HashSet::new();
+        let mut gadgets = vec![];
+        let mut pending = self.try_children()?;
+        for gadget in self.try_live_gadgets()? {
+            if seen.insert(Rc::as_ptr(&gadget)) {
+                gadgets.push(gadget);
+            }
+        }
+
+        while let Some(owner) = pending.pop() {
+            for gadget in owner.try_live_gadgets()? {
+                if seen.insert(Rc::as_ptr(&gadget)) {
+                    gadgets.push(gadget);
+                }
+            }
+            pending.extend(owner.try_children()?);
+        }
+        Ok(gadgets)
+    }
+
+    /// Like [`Owner::try_all_gadgets`], but panics on failure.
+    #[track_caller]
+    pub fn all_gadgets(&self) -> Vec<Rc<Gadget<O, G>>> {
+        self.try_all_gadgets().unwrap_or_else(fail)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn names(owners: &[Rc<Owner>]) -> Vec<String> {
+        owners.iter().map(|owner| owner.name()).collect()
+    }
+
+    #[test]
+    fn reparenting_detaches_from_the_old_parent() {
+        let old_parent = Owner::new("old");
+        let new_parent = Owner::new("new");
+        let child = Owner::new("child");
+        old_parent.add_child(&child);
+
+        new_parent.add_child(&child);
+
+        assert!(old_parent.children().is_empty());
+        assert_eq!(names(&new_parent.children()), ["child"]);
+        assert!(Rc::ptr_eq(&child.parent().unwrap(), &new_parent));
+
+        // Adding it again under the same parent does nothing.
+        new_parent.add_child(&child);
+        assert_eq!(new_parent.children().len(), 1);
+    }
+
+    #[test]
+    fn owners_cannot_be_nested_under_themselves() {
+        let org = Owner::new("org");
+        let team = Owner::new("team");
+        let person = Owner::new("person");
+        org.add_child(&team);
+        team.add_child(&person);
+
+        assert_eq!(org.try_add_child(&org), Err(Error::HierarchyCycle));
+        assert_eq!(person.try_add_child(&org), Err(Error::HierarchyCycle));
+        assert_eq!(person.try_add_child(&team), Err(Error::HierarchyCycle));
+        assert!(org.parent().is_none());
+        assert_eq!(names(&org.children()), ["team"]);
+        assert!(person.children().is_empty());
+    }
+
+    #[test]
+    fn only_direct_children_can_be_removed() {
+        let org = Owner::new("org");
+        let team = Owner::new("team");
+        let person = Owner::new("person");
+        org.add_child(&team);
+        team.add_child(&person);
+
+        assert_eq!(org.try_remove_child(&person), Err(Error::NotAChild));
+        assert!(Rc::ptr_eq(&person.parent().unwrap(), &team));
+
+        team.remove_child(&person);
+        assert!(person.parent().is_none());
+        assert!(team.children().is_empty());
+        assert_eq!(team.try_remove_child(&person), Err(Error::NotAChild));
+    }
+
+    #[test]
+    fn all_gadgets_walks_every_level_once() {
+        let org = Owner::new("org");
+        let team = Owner::new("team");
+        let person = Owner::new("person");
+        let outsider = Owner::new("outsider");
+        org.add_child(&team);
+        team.add_child(&person);
+
+        let _desk = org.add_gadget(1);
+        let _projector = team.add_gadget(2);
+        let laptop = person.add_gadget(3);
+        let _phone = outsider.add_gadget(4);
+        // Co-owned by two owners of the tree, reported once.
+        laptop.add_co_owner(&team);
+
+        let mut ids: Vec<i32> = org.all_gadgets().iter().map(|gadget| gadget.id()).collect();
+        ids.sort();
+        assert_eq!(ids, [1, 2, 3]);
+
+        let mut ids: Vec<i32> = team
+            .all_gadgets()
+            .iter()
+            .map(|gadget| gadget.id())
+            .collect();
+        ids.sort();
+        assert_eq!(ids, [2, 3]);
+    }
+
+    #[test]
+    fn children_are_freed_with_their_parent() {
+        let org = Owner::new("org");
+        let team = Owner::new("team");
+        org.add_child(&team);
+        let weak = Rc::downgrade(&team);
+        drop(team);
+
+        assert!(weak.upgrade().is_some());
+        drop(org);
+        assert!(weak.upgrade().is_none());
+    }
+}
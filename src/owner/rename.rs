@@ -0,0 +1,176 @@
+use std::time::SystemTime;
+
+use super::{fail, Event, Owner};
+use crate::error::Error;
+
+/// A name an owner used to have, see [`Owner::name_history`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct NameChange {
+    /// The previous name.
+    pub name: String,
+    /// When the owner stopped using it.
+    pub replaced_at: SystemTime,
+}
+
+impl<O, G> Owner<O, G> {
+    /// Returns the current name of this owner.
+    #[track_caller]
+    pub fn name(&self) -> String {
+        self.name.borrow().clone()
+    }
+
+    /// Changes the name of this owner in place,
+    /// so every gadget pointing at it sees the new name.
+    ///
+    /// The previous name is kept in the name history if it is enabled,
+    /// and listeners receive [`Event::OwnerRenamed`].
+    #[track_caller]
+    pub fn try_rename(&self, name: impl Into<String>) -> Result<(), Error> {
+        let mut current = self.name.try_borrow_mut()?;
+        let mut history = self.name_history.try_borrow_mut()?;
+        let new = name.into();
+        let old = std::mem::replace(&mut *current, new.clone());
+
+        let limit = self.name_history_limit.get();
+        if limit > 0 {
+            history.push_back(NameChange {
+                name: old.clone(),
+                replaced_at: SystemTime::now(),
+            });
+            while history.len() > limit {
+                history.pop_front();
+            }
+        }
+
+        drop(history);
+        drop(current);
+        self.emit(Event::OwnerRenamed { old, new });
+        Ok(())
+    }
+
+    /// Like [`Owner::try_rename`], but panics on failure.
+    #[track_caller]
+    pub fn rename(&self, name: impl Into<String>) {
+        self.try_rename(name).unwrap_or_else(fail)
+    }
+
+    /// Returns the previous names of this owner, oldest first.
+    #[track_caller]
+    pub fn name_history(&self) -> Vec<NameChange> {
+        self.name_history.borrow().iter().cloned().collect()
+    }
+
+    pub fn name_history_limit(&self) -> usize {
+        self.name_history_limit.get()
+    }
+
+    /// Keeps up to `limit` previous names; 0, the default, disables the history.
+    ///
+    /// Lowering the limit discards the oldest entries.
+    #[track_caller]
+    pub fn try_set_name_history_limit(&self, limit: usize) -> Result<(), Error> {
+        let mut history = self.name_history.try_borrow_mut()?;
+        self.name_history_limit.set(limit);
+        while history.len() > limit {
+            history.pop_front();
+        }
+        Ok(())
+    }
+
+    /// Like [`Owner::try_set_name_history_limit`], but panics on failure.
+    #[track_caller]
+    pub fn set_name_history_limit(&self, limit: usize) {
+        self.try_set_name_history_limit(limit).unwrap_or_else(fail)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    use super::*;
+
+    fn history(owner: &Owner) -> Vec<String> {
+        owner
+            .name_history()
+            .into_iter()
+            .map(|change| change.name)
+            .collect()
+    }
+
+    #[test]
+    fn history_is_off_by_default() {
+        let owner = Owner::new("first");
+        owner.rename("second");
+
+        assert_eq!(owner.name(), "second");
+        assert_eq!(owner.name_history_limit(), 0);
+        assert!(owner.name_history().is_empty());
+    }
+
+    #[test]
+    fn history_keeps_the_newest_names_in_order() {
+        let owner = Owner::new("a");
+        owner.set_name_history_limit(3);
+        let before = SystemTime::now();
+        for name in ["b", "c", "d", "e"] {
+            owner.rename(name);
+        }
+
+        assert_eq!(owner.name(), "e");
+        assert_eq!(history(&owner), ["b", "c", "d"]);
+        let changes = owner.name_history();
+        assert!(changes[0].replaced_at >= before);
+        assert!(changes
+            .windows(2)
+            .all(|pair| pair[0].replaced_at <= pair[1].replaced_at));
+    }
+
+    #[test]
+    fn lowering_the_limit_drops_the_oldest_names() {
+        let owner = Owner::new("a");
+        owner.set_name_history_limit(5);
+        for name in ["b", "c", "d"] {
+            owner.rename(name);
+        }
+
+        owner.set_name_history_limit(2);
+        assert_eq!(history(&owner), ["b", "c"]);
+        owner.set_name_history_limit(0);
+        assert!(owner.name_history().is_empty());
+        owner.rename("e");
+        assert!(owner.name_history().is_empty());
+    }
+
+    #[test]
+    fn rename_reports_the_old_and_new_names() {
+        let owner = Owner::new("before");
+        let events = Rc::new(RefCell::new(vec![]));
+        let _subscription = owner.subscribe({
+            let events = Rc::clone(&events);
+            move |owner, event| events.borrow_mut().push((owner.name(), event.clone()))
+        });
+
+        owner.rename("after");
+
+        assert_eq!(
+            *events.borrow(),
+            [(
+                "after".to_string(),
+                Event::OwnerRenamed {
+                    old: "before".to_string(),
+                    new: "after".to_string(),
+                }
+            )]
+        );
+    }
+
+    #[test]
+    fn gadgets_see_the_new_name() {
+        let owner = Owner::new("before");
+        let gadget = owner.add_gadget(1);
+        owner.rename("after");
+        assert_eq!(gadget.owner().name(), "after");
+    }
+}
@@ -0,0 +1,317 @@
+// Owners and gadgets only stay alive while something holds a strong `Rc`.
+// Loose locals work for a demo, but an application needs one place
+// that keeps the whole object graph alive and lets it be looked up:
+// the Registry holds the strong handle of every owner and gadget
+// and hands out plain ids to refer to them.
+
+use std::collections::{BTreeMap, HashMap};
+use std::rc::Rc;
+
+use crate::error::Error;
+use crate::gadget::Gadget;
+use crate::owner::{fail, Owner};
+
+/// Identifies an owner held by a [`Registry`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct OwnerId(u64);
+
+/// Identifies a gadget held by a [`Registry`].
+///
+/// Unlike [`Gadget::id`], which is only unique per owner,
+/// a handle is unique within the registry and survives transfers.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct GadgetHandle(u64);
+
+/// Holds the strong handles of owners and gadgets.
+pub struct Registry<O = (), G = ()> {
+    owners: BTreeMap<OwnerId, Rc<Owner<O, G>>>,
+    gadgets: BTreeMap<GadgetHandle, Rc<Gadget<O, G>>>,
+    // Reverse lookups; the pointers stay valid because the maps above
+    // keep every allocation alive for as long as it is indexed.
+    owner_ids: HashMap<*const Owner<O, G>, OwnerId>,
+    gadget_handles: HashMap<*const Gadget<O, G>, GadgetHandle>,
+    next_id: u64,
+}
+
+impl<O, G> Default for Registry<O, G> {
+    fn default() -> Self {
+        Registry {
+            owners: BTreeMap::new(),
+            gadgets: BTreeMap::new(),
+            owner_ids: HashMap::new(),
+            gadget_handles: HashMap::new(),
+            next_id: 0,
+        }
+    }
+}
+
+impl Registry {
+    pub fn new() -> Self {
+        Registry::default()
+    }
+
+    /// Creates an owner and keeps it in the registry.
+    pub fn create_owner(&mut self, name: impl Into<String>) -> OwnerId {
+        self.insert_owner(Owner::new(name))
+    }
+}
+
+impl<O, G> Registry<O, G> {
+    fn next_id(&mut self) -> u64 {
+        self.next_id += 1;
+        self.next_id
+    }
+
+    /// Creates an owner carrying `data` and keeps it in the registry.
+    pub fn create_owner_with(&mut self, name: impl Into<String>, data: O) -> OwnerId {
+        self.insert_owner(Owner::with_data(name, data))
+    }
+
+    /// Keeps `owner` in the registry, returning its existing id if it is already there.
+    pub fn insert_owner(&mut self, owner: Rc<Owner<O, G>>) -> OwnerId {
+        if let Some(id) = self.owner_id(&owner) {
+            return id;
+        }
+        let id = OwnerId(self.next_id());
+        self.owner_ids.insert(Rc::as_ptr(&owner), id);
+        self.owners.insert(id, owner);
+        id
+    }
+
+    pub fn owner(&self, id: OwnerId) -> Option<Rc<Owner<O, G>>> {
+        self.owners.get(&id).cloned()
+    }
+
+    pub fn owner_id(&self, owner: &Rc<Owner<O, G>>) -> Option<OwnerId> {
+        self.owner_ids.get(&Rc::as_ptr(owner)).copied()
+    }
+
+    /// Finds the first owner, in creation order, currently named `name`.
+    pub fn find_owner(&self, name: &str) -> Option<OwnerId> {
+        self.owners
+            .iter()
+            .find(|(_, owner)| owner.name() == name)
+            .map(|(id, _)| *id)
+    }
+
+    /// Iterates over the owners in creation order.
+    pub fn owners(&self) -> impl Iterator<Item = (OwnerId, &Rc<Owner<O, G>>)> {
+        self.owners.iter().map(|(id, owner)| (*id, owner))
+    }
+
+    /// Removes an owner from the registry and returns its strong handle.
+    ///
+    /// Fails with [`Error::UnknownOwner`] if `id` is not in the registry,
+    /// and with [`Error::OwnerInUse`] while any live gadget still lists the owner:
+    /// those gadgets would keep it alive anyway, so remove or transfer them first.
+    pub fn try_remove_owner(&mut self, id: OwnerId) -> Result<Rc<Owner<O, G>>, Error> {
+        let owner = self.owners.get(&id).ok_or(Error::UnknownOwner)?;
+        if owner.try_live_gadgets()?.next().is_some() {
+            return Err(Error::OwnerInUse);
+        }
+        let owner = self.owners.remove(&id).unwrap();
+        self.owner_ids.remove(&Rc::as_ptr(&owner));
+        Ok(owner)
+    }
+
+    /// Like [`Registry::try_remove_owner`], but panics on failure.
+    pub fn remove_owner(&mut self, id: OwnerId) -> Rc<Owner<O, G>> {
+        self.try_remove_owner(id).unwrap_or_else(fail)
+    }
+
+    /// Creates a gadget carrying `data` on the owner `owner` and keeps it in the registry.
+    ///
+    /// Fails with [`Error::UnknownOwner`] if `owner` is not in the registry,
+    /// or with any error of [`Owner::try_add_gadget_with`].
+    pub fn try_add_gadget_with(
+        &mut self,
+        owner: OwnerId,
+        id: i32,
+        data: G,
+    ) -> Result<GadgetHandle, Error> {
+        let owner = self.owners.get(&owner).ok_or(Error::UnknownOwner)?;
+        let gadget = owner.try_add_gadget_with(id, data)?;
+        Ok(self.insert_gadget(gadget))
+    }
+
+    /// Like [`Registry::try_add_gadget_with`], but panics on failure.
+    pub fn add_gadget_with(&mut self, owner: OwnerId, id: i32, data: G) -> GadgetHandle {
+        self.try_add_gadget_with(owner, id, data)
+            .unwrap_or_else(fail)
+    }
+
+    /// Keeps `gadget` in the registry, returning its existing handle if it is already there.
+    ///
+    /// The gadget's owners are not added; use [`Registry::insert_owner`] for them.
+    pub fn insert_gadget(&mut self, gadget: Rc<Gadget<O, G>>) -> GadgetHandle {
+        if let Some(handle) = self.gadget_handle(&gadget) {
+            return handle;
+        }
+        let handle = GadgetHandle(self.next_id());
+        self.gadget_handles.insert(Rc::as_ptr(&gadget), handle);
+        self.gadgets.insert(handle, gadget);
+        handle
+    }
+
+    pub fn gadget(&self, handle: GadgetHandle) -> Option<Rc<Gadget<O, G>>> {
+        self.gadgets.get(&handle).cloned()
+    }
+
+    pub fn gadget_handle(&self, gadget: &Rc<Gadget<O, G>>) -> Option<GadgetHandle> {
+        self.gadget_handles.get(&Rc::as_ptr(gadget)).copied()
+    }
+
+    /// Finds the gadget registered under `id` on the owner `owner`.
+    pub fn find_gadget(&self, owner: OwnerId, id: i32) -> Option<GadgetHandle> {
+        let gadget = self.owners.get(&owner)?.gadget(id)?;
+        self.gadget_handle(&gadget)
+    }
+
+    /// Iterates over the gadgets in creation order.
+    pub fn gadgets(&self) -> impl Iterator<Item = (GadgetHandle, &Rc<Gadget<O, G>>)> {
+        self.gadgets
+            .iter()
+            .map(|(handle, gadget)| (*handle, gadget))
+    }
+
+    /// Removes a gadget from the registry and returns its strong handle.
+    ///
+    /// Dropping the returned `Rc` frees the gadget
+    /// unless something outside the registry still holds it.
+    pub fn remove_gadget(&mut self, handle: GadgetHandle) -> Option<Rc<Gadget<O, G>>> {
+        let gadget = self.gadgets.remove(&handle)?;
+        self.gadget_handles.remove(&Rc::as_ptr(&gadget));
+        Some(gadget)
+    }
+
+    pub fn owner_count(&self) -> usize {
+        self.owners.len()
+    }
+
+    pub fn gadget_count(&self) -> usize {
+        self.gadgets.len()
+    }
+}
+
+impl<O, G: Default> Registry<O, G> {
+    /// Creates a gadget with a default payload, see [`Registry::try_add_gadget_with`].
+    pub fn try_add_gadget(&mut self, owner: OwnerId, id: i32) -> Result<GadgetHandle, Error> {
+        self.try_add_gadget_with(owner, id, G::default())
+    }
+
+    /// Creates a gadget with a default payload, see [`Registry::add_gadget_with`].
+    pub fn add_gadget(&mut self, owner: OwnerId, id: i32) -> GadgetHandle {
+        self.add_gadget_with(owner, id, G::default())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn owners_in_use_cannot_be_removed() {
+        let mut registry = Registry::new();
+        let ann = registry.create_owner("Ann");
+        let gadget = registry.add_gadget(ann, 1);
+
+        assert_eq!(
+            registry.try_remove_owner(ann).err(),
+            Some(Error::OwnerInUse)
+        );
+        assert_eq!(registry.owner_count(), 1);
+
+        drop(registry.remove_gadget(gadget));
+        let owner = registry.remove_owner(ann);
+        assert_eq!(owner.name(), "Ann");
+        assert_eq!(Rc::strong_count(&owner), 1);
+        assert!(registry.owner(ann).is_none());
+        assert_eq!(registry.owner_id(&owner), None);
+        assert_eq!(registry.owner_count(), 0);
+    }
+
+    #[test]
+    fn unknown_owners_are_rejected() {
+        let mut registry = Registry::new();
+        let ann = registry.create_owner("Ann");
+        registry.remove_owner(ann);
+
+        assert_eq!(
+            registry.try_remove_owner(ann).err(),
+            Some(Error::UnknownOwner)
+        );
+        assert_eq!(registry.try_add_gadget(ann, 1), Err(Error::UnknownOwner));
+        assert_eq!(registry.find_gadget(ann, 1), None);
+    }
+
+    #[test]
+    fn inserting_twice_returns_the_same_id() {
+        let mut registry = Registry::new();
+        let owner = Owner::new("Ann");
+        let id = registry.insert_owner(Rc::clone(&owner));
+        assert_eq!(registry.insert_owner(Rc::clone(&owner)), id);
+        assert_eq!(registry.owner_id(&owner), Some(id));
+        assert_eq!(registry.owner_count(), 1);
+
+        let gadget = owner.add_gadget(1);
+        let handle = registry.insert_gadget(Rc::clone(&gadget));
+        assert_eq!(registry.insert_gadget(Rc::clone(&gadget)), handle);
+        assert_eq!(registry.gadget_handle(&gadget), Some(handle));
+        assert_eq!(registry.gadget_count(), 1);
+    }
+
+    #[test]
+    fn gadgets_are_found_by_owner_and_id() {
+        let mut registry = Registry::new();
+        let ann = registry.create_owner("Ann");
+        let bob = registry.create_owner("Bob");
+        let ann_1 = registry.add_gadget(ann, 1);
+        let bob_1 = registry.add_gadget(bob, 1);
+
+        assert_ne!(ann_1, bob_1);
+        assert_eq!(registry.find_gadget(ann, 1), Some(ann_1));
+        assert_eq!(registry.find_gadget(bob, 1), Some(bob_1));
+        assert_eq!(registry.find_gadget(ann, 2), None);
+        assert_eq!(registry.find_owner("Bob"), Some(bob));
+        assert_eq!(registry.find_owner("Eve"), None);
+        assert_eq!(registry.try_add_gadget(ann, 1), Err(Error::DuplicateId(1)));
+    }
+
+    #[test]
+    fn removed_gadgets_are_freed_and_deregistered() {
+        let mut registry = Registry::new();
+        let ann = registry.create_owner("Ann");
+        let handle = registry.add_gadget(ann, 1);
+        let _other = registry.add_gadget(ann, 2);
+        let weak = Rc::downgrade(&registry.gadget(handle).unwrap());
+
+        drop(registry.remove_gadget(handle));
+
+        assert!(weak.upgrade().is_none());
+        assert!(registry.gadget(handle).is_none());
+        assert_eq!(
+            registry.remove_gadget(handle).map(|gadget| gadget.id()),
+            None
+        );
+        let owner = registry.owner(ann).unwrap();
+        assert!(owner.gadget(1).is_none());
+        assert_eq!(owner.gadgets().len(), 1);
+        assert_eq!(owner.dangling_count(), 0);
+        assert_eq!(registry.find_gadget(ann, 1), None);
+        assert_eq!(registry.gadget_count(), 1);
+    }
+
+    #[test]
+    fn listing_follows_creation_order() {
+        let mut registry = Registry::new();
+        let ann = registry.create_owner("Ann");
+        let bob = registry.create_owner("Bob");
+        let first = registry.add_gadget(bob, 1);
+        let second = registry.add_gadget(ann, 1);
+
+        let owners: Vec<OwnerId> = registry.owners().map(|(id, _)| id).collect();
+        assert_eq!(owners, [ann, bob]);
+        let gadgets: Vec<GadgetHandle> = registry.gadgets().map(|(handle, _)| handle).collect();
+        assert_eq!(gadgets, [first, second]);
+    }
+}
@@ -0,0 +1,594 @@
+// A compact, versioned binary image of a whole `Registry`,
+// for graphs too large to load through JSON at startup.
+//
+// Layout, all integers little-endian:
+//
+//     header   magic "RCGS", version: u16, flags: u16 (0),
+//              string count: u32, owner count: u32, gadget count: u32
+//     strings  per string: byte length: u32, UTF-8 bytes
+//     owners   per owner: name: string index, parent: owner index or u32::MAX,
+//              payload
+//     gadgets  per gadget: id: i32, owner count: u32, owner indices (primary first),
+//              status: u8, label: string index,
+//              tag count: u32, per tag: key and value string indices,
+//              payload
+//     trailer  CRC-32 (IEEE) of every byte before it: u32
+//
+// Owner names, labels and tags go through the string table,
+// so a name shared by many owners or a tag used on every gadget is stored once.
+// Owners and gadgets refer to each other by their position in the file,
+// and the loader rebuilds the `Rc<Owner>` back-references and `Weak<Gadget>`
+// lists through the regular API, so every invariant of the live graph holds.
+
+use std::collections::HashMap;
+use std::rc::Rc;
+
+use crate::error::Error;
+use crate::gadget::GadgetStatus;
+use crate::owner::{fail, Owner};
+use crate::registry::Registry;
+
+const MAGIC: &[u8; 4] = b"RCGS";
+const VERSION: u16 = 1;
+const NO_PARENT: u32 = u32::MAX;
+
+/// Encodes a payload into a snapshot and decodes it back.
+pub trait SnapshotPayload: Sized {
+    fn encode(&self, out: &mut Vec<u8>);
+
+    /// Fails with [`Error::InvalidSnapshot`] if `input` does not start with a valid `Self`.
+    /// Advances `input` past the bytes consumed.
+    fn decode(input: &mut Reader<'_>) -> Result<Self, Error>;
+}
+
+impl SnapshotPayload for () {
+    fn encode(&self, _out: &mut Vec<u8>) {}
+
+    fn decode(_input: &mut Reader<'_>) -> Result<Self, Error> {
+        Ok(())
+    }
+}
+
+impl SnapshotPayload for bool {
+    fn encode(&self, out: &mut Vec<u8>) {
+        out.push(u8::from(*self));
+    }
+
+    fn decode(input: &mut Reader<'_>) -> Result<Self, Error> {
+        match input.u8()? {
+            0 => Ok(false),
+            1 => Ok(true),
+            _ => Err(invalid("invalid boolean")),
+        }
+    }
+}
+
+macro_rules! snapshot_number {
+    ($($ty:ty),*) => {$(
+        impl SnapshotPayload for $ty {
+            fn encode(&self, out: &mut Vec<u8>) {
+                out.extend_from_slice(&self.to_le_bytes());
+            }
+
+            fn decode(input: &mut Reader<'_>) -> Result<Self, Error> {
+                let bytes = input.bytes(std::mem::size_of::<$ty>())?;
+                Ok(<$ty>::from_le_bytes(bytes.try_into().unwrap()))
+            }
+        }
+    )*};
+}
+
+snapshot_number!(i32, i64, u32, u64, f64);
+
+impl SnapshotPayload for String {
+    fn encode(&self, out: &mut Vec<u8>) {
+        (self.len() as u32).encode(out);
+        out.extend_from_slice(self.as_bytes());
+    }
+
+    fn decode(input: &mut Reader<'_>) -> Result<Self, Error> {
+        input.string()
+    }
+}
+
+/// Reads the sections of a snapshot, see [`SnapshotPayload::decode`].
+pub struct Reader<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    pub fn bytes(&mut self, len: usize) -> Result<&'a [u8], Error> {
+        if self.bytes.len() < len {
+            return Err(invalid("unexpected end of snapshot"));
+        }
+        let (head, tail) = self.bytes.split_at(len);
+        self.bytes = tail;
+        Ok(head)
+    }
+
+    pub fn u8(&mut self) -> Result<u8, Error> {
+        Ok(self.bytes(1)?[0])
+    }
+
+    pub fn u16(&mut self) -> Result<u16, Error> {
+        Ok(u16::from_le_bytes(self.bytes(2)?.try_into().unwrap()))
+    }
+
+    pub fn u32(&mut self) -> Result<u32, Error> {
+        u32::decode(self)
+    }
+
+    pub fn string(&mut self) -> Result<String, Error> {
+        let len = self.u32()? as usize;
+        let bytes = self.bytes(len)?;
+        String::from_utf8(bytes.to_vec()).map_err(|_| invalid("string is not valid UTF-8"))
+    }
+}
+
+fn invalid(message: &str) -> Error {
+    Error::InvalidSnapshot(message.to_string())
+}
+
+// CRC-32 with the IEEE polynomial, as used by zip and PNG.
+const CRC_TABLE: [u32; 256] = {
+    let mut table = [0; 256];
+    let mut i = 0;
+    while i < 256 {
+        let mut crc = i as u32;
+        let mut bit = 0;
+        while bit < 8 {
+            crc = if crc & 1 == 1 {
+                (crc >> 1) ^ 0xedb8_8320
+            } else {
+                crc >> 1
+            };
+            bit += 1;
+        }
+        table[i] = crc;
+        i += 1;
+    }
+    table
+};
+
+fn crc32(bytes: &[u8]) -> u32 {
+    !bytes.iter().fold(!0, |crc, byte| {
+        CRC_TABLE[((crc ^ u32::from(*byte)) & 0xff) as usize] ^ (crc >> 8)
+    })
+}
+
+fn status_code(status: GadgetStatus) -> u8 {
+    match status {
+        GadgetStatus::Active => 0,
+        GadgetStatus::Retired => 1,
+        GadgetStatus::Lost => 2,
+    }
+}
+
+fn status_from_code(code: u8) -> Result<GadgetStatus, Error> {
+    match code {
+        0 => Ok(GadgetStatus::Active),
+        1 => Ok(GadgetStatus::Retired),
+        2 => Ok(GadgetStatus::Lost),
+        _ => Err(invalid("invalid gadget status")),
+    }
+}
+
+#[derive(Default)]
+struct StringTable {
+    strings: Vec<String>,
+    indices: HashMap<String, u32>,
+}
+
+impl StringTable {
+    fn intern(&mut self, value: String) -> u32 {
+        if let Some(index) = self.indices.get(&value) {
+            return *index;
+        }
+        let index = self.strings.len() as u32;
+        self.indices.insert(value.clone(), index);
+        self.strings.push(value);
+        index
+    }
+}
+
+impl<O: SnapshotPayload, G: SnapshotPayload> Registry<O, G> {
+    /// Writes every owner and gadget of the registry into a binary snapshot.
+    ///
+    /// Fails with [`Error::UnknownOwner`] if a gadget of the registry
+    /// is owned by an owner that is not in it.
+    pub fn try_to_snapshot(&self) -> Result<Vec<u8>, Error> {
+        let mut strings = StringTable::default();
+        let mut body = vec![];
+
+        let mut positions = HashMap::new();
+        for (position, (_, owner)) in self.owners().enumerate() {
+            positions.insert(Rc::as_ptr(owner), position as u32);
+        }
+        for (_, owner) in self.owners() {
+            strings.intern(owner.name()).encode(&mut body);
+            let parent = owner
+                .parent()
+                .and_then(|parent| positions.get(&Rc::as_ptr(&parent)).copied())
+                .unwrap_or(NO_PARENT);
+            parent.encode(&mut body);
+            owner.data().encode(&mut body);
+        }
+
+        for (_, gadget) in self.gadgets() {
+            gadget.id().encode(&mut body);
+            let owners = gadget.owners();
+            (owners.len() as u32).encode(&mut body);
+            for owner in &owners {
+                positions
+                    .get(&Rc::as_ptr(owner))
+                    .ok_or(Error::UnknownOwner)?
+                    .encode(&mut body);
+            }
+            body.push(status_code(gadget.status()));
+            strings.intern(gadget.label()).encode(&mut body);
+            let tags = gadget.tags();
+            (tags.len() as u32).encode(&mut body);
+            for (key, value) in tags {
+                strings.intern(key).encode(&mut body);
+                strings.intern(value).encode(&mut body);
+            }
+            gadget.data().encode(&mut body);
+        }
+
+        let mut out = Vec::with_capacity(body.len() + 64);
+        out.extend_from_slice(MAGIC);
+        out.extend_from_slice(&VERSION.to_le_bytes());
+        out.extend_from_slice(&0u16.to_le_bytes());
+        (strings.strings.len() as u32).encode(&mut out);
+        (self.owner_count() as u32).encode(&mut out);
+        (self.gadget_count() as u32).encode(&mut out);
+        for string in &strings.strings {
+            string.encode(&mut out);
+        }
+        out.extend_from_slice(&body);
+        crc32(&out).encode(&mut out);
+        Ok(out)
+    }
+
+    /// Like [`Registry::try_to_snapshot`], but panics on failure.
+    pub fn to_snapshot(&self) -> Vec<u8> {
+        self.try_to_snapshot().unwrap_or_else(fail)
+    }
+
+    /// Rebuilds a registry from a snapshot written by [`Registry::to_snapshot`].
+    ///
+    /// Fails with [`Error::InvalidSnapshot`] if the snapshot is truncated,
+    /// corrupted or written by an unsupported version,
+    /// or if it describes a cycle of parents or two gadgets of one owner sharing an id.
+    pub fn from_snapshot(bytes: &[u8]) -> Result<Registry<O, G>, Error> {
+        if bytes.len() < 4 {
+            return Err(invalid("snapshot is too short"));
+        }
+        let (contents, checksum) = bytes.split_at(bytes.len() - 4);
+        if crc32(contents) != u32::from_le_bytes(checksum.try_into().unwrap()) {
+            return Err(invalid("checksum mismatch"));
+        }
+
+        let mut input = Reader { bytes: contents };
+        if input.bytes(4)? != MAGIC {
+            return Err(invalid("not a gadget graph snapshot"));
+        }
+        let version = input.u16()?;
+        if version != VERSION {
+            return Err(Error::InvalidSnapshot(format!(
+                "unsupported version {version}"
+            )));
+        }
+        if input.u16()? != 0 {
+            return Err(invalid("unknown flags"));
+        }
+        let string_count = input.u32()?;
+        let owner_count = input.u32()?;
+        let gadget_count = input.u32()?;
+
+        // Counts come from the file: cap the preallocation by what the input could hold.
+        let capacity = |count: u32| (count as usize).min(contents.len());
+        let mut strings = Vec::with_capacity(capacity(string_count));
+        for _ in 0..string_count {
+            strings.push(input.string()?);
+        }
+        let string = |index: u32| {
+            strings
+                .get(index as usize)
+                .ok_or_else(|| invalid("string index out of range"))
+        };
+
+        let mut registry = Registry::default();
+        let mut owners: Vec<Rc<Owner<O, G>>> = Vec::with_capacity(capacity(owner_count));
+        let mut parents = Vec::with_capacity(capacity(owner_count));
+        for _ in 0..owner_count {
+            let name = string(input.u32()?)?;
+            parents.push(input.u32()?);
+            let owner = Owner::with_data(name.as_str(), O::decode(&mut input)?);
+            registry.insert_owner(Rc::clone(&owner));
+            owners.push(owner);
+        }
+        let owner = |index: u32| {
+            owners
+                .get(index as usize)
+                .ok_or_else(|| invalid("owner index out of range"))
+        };
+        for (child, parent) in owners.iter().zip(parents) {
+            if parent != NO_PARENT {
+                owner(parent)?
+                    .try_add_child(child)
+                    .map_err(|_| invalid("owners form a cycle"))?;
+            }
+        }
+
+        for _ in 0..gadget_count {
+            let id = i32::decode(&mut input)?;
+            let owner_refs = input.u32()?;
+            if owner_refs == 0 {
+                return Err(invalid("gadget without owner"));
+            }
+            let mut gadget_owners = Vec::with_capacity(capacity(owner_refs));
+            for _ in 0..owner_refs {
+                gadget_owners.push(owner(input.u32()?)?);
+            }
+            let status = status_from_code(input.u8()?)?;
+            let label = string(input.u32()?)?;
+            let tag_count = input.u32()?;
+            let mut tags = Vec::with_capacity(capacity(tag_count));
+            for _ in 0..tag_count {
+                tags.push((string(input.u32()?)?, string(input.u32()?)?));
+            }
+            let data = G::decode(&mut input)?;
+
+            let duplicate = |_| Error::InvalidSnapshot(format!("duplicate gadget id {id}"));
+            let gadget = gadget_owners[0]
+                .try_add_gadget_with(id, data)
+                .map_err(duplicate)?;
+            for co_owner in &gadget_owners[1..] {
+                gadget.try_add_co_owner(co_owner).map_err(duplicate)?;
+            }
+            gadget.set_status(status);
+            gadget.set_label(label.as_str());
+            for (key, value) in tags {
+                gadget.set_tag(key.as_str(), value.as_str());
+            }
+            registry.insert_gadget(gadget);
+        }
+
+        if !input.bytes.is_empty() {
+            return Err(invalid("trailing bytes"));
+        }
+        Ok(registry)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    type TestRegistry = Registry<String, u64>;
+
+    fn sample() -> TestRegistry {
+        let mut registry = TestRegistry::default();
+        let org = registry.create_owner_with("org", "headquarters".to_string());
+        let team = registry.create_owner_with("team", String::new());
+        let person = registry.create_owner_with("Zoë", "desk 4".to_string());
+        registry
+            .owner(org)
+            .unwrap()
+            .add_child(&registry.owner(team).unwrap());
+        registry
+            .owner(team)
+            .unwrap()
+            .add_child(&registry.owner(person).unwrap());
+
+        let projector = registry.add_gadget_with(team, 1, 500);
+        let laptop = registry.add_gadget_with(person, 1, 1200);
+        registry.add_gadget_with(org, 7, 0);
+
+        let projector = registry.gadget(projector).unwrap();
+        projector.add_co_owner(&registry.owner(org).unwrap());
+        projector.set_label("meeting room");
+        projector.set_tag("room", "4B");
+        projector.set_tag("floor", "4");
+        let laptop = registry.gadget(laptop).unwrap();
+        laptop.set_status(GadgetStatus::Lost);
+        laptop.set_label("meeting room");
+        laptop.set_tag("room", "4B");
+        registry
+    }
+
+    fn names(owners: &[Rc<Owner<String, u64>>]) -> Vec<String> {
+        owners.iter().map(|owner| owner.name()).collect()
+    }
+
+    // Replaces the checksum after the contents were edited,
+    // so the checks behind it are reached.
+    fn reseal(mut bytes: Vec<u8>, edit: impl FnOnce(&mut Vec<u8>)) -> Vec<u8> {
+        bytes.truncate(bytes.len() - 4);
+        edit(&mut bytes);
+        let checksum = crc32(&bytes);
+        checksum.encode(&mut bytes);
+        bytes
+    }
+
+    fn load_error(bytes: &[u8]) -> Error {
+        match TestRegistry::from_snapshot(bytes) {
+            Ok(_) => panic!("snapshot was accepted"),
+            Err(err) => err,
+        }
+    }
+
+    #[test]
+    fn round_trip_keeps_the_whole_graph() {
+        let bytes = sample().to_snapshot();
+        let registry = TestRegistry::from_snapshot(&bytes).unwrap();
+
+        let owners: Vec<_> = registry
+            .owners()
+            .map(|(_, owner)| Rc::clone(owner))
+            .collect();
+        assert_eq!(names(&owners), ["org", "team", "Zoë"]);
+        assert_eq!(owners[0].data(), "headquarters");
+        assert_eq!(owners[2].data(), "desk 4");
+        assert!(owners[0].parent().is_none());
+        assert!(Rc::ptr_eq(&owners[1].parent().unwrap(), &owners[0]));
+        assert!(Rc::ptr_eq(&owners[2].parent().unwrap(), &owners[1]));
+        assert_eq!(names(&owners[0].children()), ["team"]);
+
+        let gadgets: Vec<_> = registry
+            .gadgets()
+            .map(|(_, gadget)| Rc::clone(gadget))
+            .collect();
+        assert_eq!(gadgets.len(), 3);
+        let projector = &gadgets[0];
+        assert_eq!((projector.id(), *projector.data()), (1, 500));
+        assert_eq!(names(&projector.owners()), ["team", "org"]);
+        assert_eq!(projector.label(), "meeting room");
+        assert_eq!(projector.tag("floor").as_deref(), Some("4"));
+        assert_eq!(projector.status(), GadgetStatus::Active);
+        let laptop = &gadgets[1];
+        assert_eq!((laptop.id(), *laptop.data()), (1, 1200));
+        assert_eq!(names(&laptop.owners()), ["Zoë"]);
+        assert_eq!(laptop.status(), GadgetStatus::Lost);
+        assert_eq!(laptop.tags().len(), 1);
+
+        // The `Weak` lists are rebuilt, co-owners included.
+        let ids =
+            |owner: &Owner<String, u64>| owner.live_gadgets().map(|g| g.id()).collect::<Vec<_>>();
+        assert_eq!(ids(&owners[0]), [1, 7]);
+        assert_eq!(ids(&owners[1]), [1]);
+        assert!(Rc::ptr_eq(&owners[0].gadget(1).unwrap(), projector));
+        assert!(Rc::ptr_eq(&owners[2].gadget(1).unwrap(), laptop));
+
+        assert_eq!(registry.to_snapshot(), bytes);
+    }
+
+    #[test]
+    fn empty_registry_round_trips() {
+        let bytes = Registry::new().to_snapshot();
+        let registry = Registry::<(), ()>::from_snapshot(&bytes).unwrap();
+        assert_eq!((registry.owner_count(), registry.gadget_count()), (0, 0));
+    }
+
+    #[test]
+    fn flipped_bits_are_detected() {
+        let bytes = sample().to_snapshot();
+        for position in 0..bytes.len() {
+            for bit in 0..8 {
+                let mut corrupted = bytes.clone();
+                corrupted[position] ^= 1 << bit;
+                assert!(
+                    matches!(load_error(&corrupted), Error::InvalidSnapshot(_)),
+                    "bit {bit} of byte {position}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn truncation_is_detected() {
+        let bytes = sample().to_snapshot();
+        for len in 0..bytes.len() {
+            assert!(
+                matches!(load_error(&bytes[..len]), Error::InvalidSnapshot(_)),
+                "truncated to {len} bytes"
+            );
+        }
+        let resealed = reseal(bytes, |contents| {
+            contents.pop();
+        });
+        assert_eq!(
+            load_error(&resealed),
+            Error::InvalidSnapshot("unexpected end of snapshot".to_string())
+        );
+    }
+
+    #[test]
+    fn wrong_magic_is_rejected() {
+        let bytes = reseal(sample().to_snapshot(), |contents| {
+            contents[..4].copy_from_slice(b"JSON")
+        });
+        assert_eq!(
+            load_error(&bytes),
+            Error::InvalidSnapshot("not a gadget graph snapshot".to_string())
+        );
+    }
+
+    #[test]
+    fn wrong_version_is_rejected() {
+        let bytes = reseal(sample().to_snapshot(), |contents| {
+            contents[4..6].copy_from_slice(&2u16.to_le_bytes())
+        });
+        assert_eq!(
+            load_error(&bytes),
+            Error::InvalidSnapshot("unsupported version 2".to_string())
+        );
+    }
+
+    #[test]
+    fn trailing_bytes_are_rejected() {
+        let bytes = reseal(sample().to_snapshot(), |contents| contents.push(0));
+        assert_eq!(
+            load_error(&bytes),
+            Error::InvalidSnapshot("trailing bytes".to_string())
+        );
+        let mut bytes = sample().to_snapshot();
+        bytes.push(0);
+        assert_eq!(
+            load_error(&bytes),
+            Error::InvalidSnapshot("checksum mismatch".to_string())
+        );
+    }
+
+    #[test]
+    fn parent_cycles_are_rejected() {
+        let mut registry = TestRegistry::default();
+        let org = registry.create_owner_with("org", String::new());
+        let team = registry.create_owner_with("team", String::new());
+        registry
+            .owner(org)
+            .unwrap()
+            .add_child(&registry.owner(team).unwrap());
+
+        // Point the root at its own child.
+        let bytes = reseal(registry.to_snapshot(), |contents| {
+            let no_parent = NO_PARENT.to_le_bytes();
+            let at = contents
+                .windows(4)
+                .position(|window| window == no_parent)
+                .unwrap();
+            contents[at..at + 4].copy_from_slice(&1u32.to_le_bytes());
+        });
+        assert_eq!(
+            load_error(&bytes),
+            Error::InvalidSnapshot("owners form a cycle".to_string())
+        );
+    }
+
+    #[test]
+    fn duplicate_gadget_ids_are_rejected() {
+        let mut registry = TestRegistry::default();
+        let org = registry.create_owner_with("org", String::new());
+        registry.add_gadget_with(org, 1, 0);
+        registry.add_gadget_with(org, 2, 0);
+
+        let bytes = reseal(registry.to_snapshot(), |contents| {
+            let second = 2i32.to_le_bytes();
+            let at = contents
+                .windows(4)
+                .rposition(|window| window == second)
+                .unwrap();
+            contents[at..at + 4].copy_from_slice(&1i32.to_le_bytes());
+        });
+        assert_eq!(
+            load_error(&bytes),
+            Error::InvalidSnapshot("duplicate gadget id 1".to_string())
+        );
+    }
+
+    #[test]
+    fn checksum_matches_the_ieee_crc() {
+        assert_eq!(crc32(b"123456789"), 0xcbf4_3926);
+    }
+}
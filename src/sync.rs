@@ -0,0 +1,16 @@
+// A thread-safe counterpart of the Owner–Gadget model.
+//
+// `Rc` and `RefCell` are neither `Send` nor `Sync`:
+// their reference counts and borrow flags are updated without synchronisation.
+// Here `Arc` replaces `Rc`, `std::sync::Weak` replaces `rc::Weak`,
+// and an `RwLock` takes the place of the `RefCell` around the gadget list,
+// so owners and gadgets can be shared across threads.
+//
+// Gadget ids are unique per owner and indexed for lookup, as in the `Rc` model;
+// co-owners, transfers, hierarchies and events are not available here.
+
+mod gadget;
+mod owner;
+
+pub use gadget::Gadget;
+pub use owner::Owner;
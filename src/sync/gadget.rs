@@ -0,0 +1,33 @@
+use std::sync::Arc;
+
+use super::owner::Owner;
+
+pub struct Gadget<O = (), G = ()> {
+    id: i32,
+    data: G,
+    owner: Arc<Owner<O, G>>,
+}
+
+impl<O, G> Gadget<O, G> {
+    pub(crate) fn new(id: i32, data: G, owner: Arc<Owner<O, G>>) -> Self {
+        Gadget { id, data, owner }
+    }
+
+    pub fn id(&self) -> i32 {
+        self.id
+    }
+
+    pub fn data(&self) -> &G {
+        &self.data
+    }
+
+    pub fn owner(&self) -> &Arc<Owner<O, G>> {
+        &self.owner
+    }
+}
+
+impl<O, G> Drop for Gadget<O, G> {
+    fn drop(&mut self) {
+        self.owner.deregister(self);
+    }
+}
@@ -0,0 +1,315 @@
+use std::sync::{Arc, Mutex, PoisonError, RwLock, RwLockWriteGuard, Weak};
+
+use super::gadget::Gadget;
+use crate::error::Error;
+use crate::gadget_list::GadgetList;
+use crate::owner::fail;
+use crate::PrunePolicy;
+
+pub struct Owner<O = (), G = ()> {
+    name: String,
+    data: O,
+    // Same as the single-threaded owner: only `Weak` pointers to gadgets,
+    // so the strong `Arc<Owner>` held by each gadget does not form a cycle.
+    // The list is also indexed by `Gadget::id`.
+    gadgets: RwLock<GadgetList<Weak<Gadget<O, G>>>>,
+    prune_policy: Mutex<PrunePolicy>,
+    // Lower bound for the next id handed out by `Owner::next_id`,
+    // only updated under the write lock of `gadgets`.
+    next_id: Mutex<i32>,
+}
+
+impl Owner {
+    /// Creates a new owner without any gadgets.
+    pub fn new(name: impl Into<String>) -> Arc<Owner> {
+        Owner::with_data(name, ())
+    }
+}
+
+impl<O, G> Owner<O, G> {
+    /// Creates a new owner carrying `data` as its payload.
+    pub fn with_data(name: impl Into<String>, data: O) -> Arc<Self> {
+        Arc::new(Owner {
+            name: name.into(),
+            data,
+            gadgets: RwLock::new(GadgetList::new()),
+            prune_policy: Mutex::new(PrunePolicy::default()),
+            next_id: Mutex::new(1),
+        })
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn data(&self) -> &O {
+        &self.data
+    }
+
+    fn write_gadgets(&self) -> RwLockWriteGuard<'_, GadgetList<Weak<Gadget<O, G>>>> {
+        self.gadgets.write().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Creates a gadget carrying `data`, owned by this owner, and registers it in one step.
+    ///
+    /// The write lock is held while the `Weak` is pushed,
+    /// so concurrent callers never observe a half-registered gadget.
+    ///
+    /// Fails with [`Error::DuplicateId`] if a live gadget with the same id
+    /// is already registered on this owner.
+    pub fn try_add_gadget_with(
+        self: &Arc<Self>,
+        id: i32,
+        data: G,
+    ) -> Result<Arc<Gadget<O, G>>, Error> {
+        let mut gadgets = self.write_gadgets();
+        self.register(&mut gadgets, id, data)
+    }
+
+    /// Like [`Owner::try_add_gadget_with`], but panics on failure.
+    pub fn add_gadget_with(self: &Arc<Self>, id: i32, data: G) -> Arc<Gadget<O, G>> {
+        self.try_add_gadget_with(id, data).unwrap_or_else(fail)
+    }
+
+    // Registers a new gadget while the caller holds the write lock.
+    fn register(
+        self: &Arc<Self>,
+        gadgets: &mut GadgetList<Weak<Gadget<O, G>>>,
+        id: i32,
+        data: G,
+    ) -> Result<Arc<Gadget<O, G>>, Error> {
+        if gadgets
+            .get(id)
+            .is_some_and(|gadget| gadget.strong_count() > 0)
+        {
+            return Err(Error::DuplicateId(id));
+        }
+        let gadget = Arc::new(Gadget::new(id, data, Arc::clone(self)));
+
+        gadgets.push(id, Arc::downgrade(&gadget));
+        Ok(gadget)
+    }
+
+    /// Hands out the lowest id, at or above the previously allocated one,
+    /// that no live gadget of this owner is using.
+    ///
+    /// Another thread may register the id before the caller does;
+    /// [`Owner::add_next_gadget_with`] allocates and registers under one lock.
+    pub fn next_id(&self) -> i32 {
+        let gadgets = self.write_gadgets();
+        self.allocate_id(&gadgets)
+    }
+
+    fn allocate_id(&self, gadgets: &GadgetList<Weak<Gadget<O, G>>>) -> i32 {
+        let mut next_id = self.next_id.lock().unwrap_or_else(PoisonError::into_inner);
+        let mut id = *next_id;
+        while gadgets
+            .get(id)
+            .is_some_and(|gadget| gadget.strong_count() > 0)
+        {
+            id += 1;
+        }
+        *next_id = id + 1;
+        id
+    }
+
+    /// Creates and registers a gadget under an id picked by [`Owner::next_id`],
+    /// without letting another thread take the id in between.
+    pub fn add_next_gadget_with(self: &Arc<Self>, data: G) -> Arc<Gadget<O, G>> {
+        let mut gadgets = self.write_gadgets();
+        let id = self.allocate_id(&gadgets);
+        // The id is free and the lock is still held, so this cannot fail.
+        self.register(&mut gadgets, id, data).unwrap_or_else(fail)
+    }
+
+    /// Returns a snapshot of the `Weak` pointers to this owner's gadgets.
+    ///
+    /// The read lock is only held while copying,
+    /// so holding on to the snapshot never blocks other threads.
+    pub fn gadgets(&self) -> Vec<Weak<Gadget<O, G>>> {
+        self.gadgets
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .entries()
+            .cloned()
+            .collect()
+    }
+
+    /// Looks up a live gadget by its id.
+    pub fn gadget(&self, id: i32) -> Option<Arc<Gadget<O, G>>> {
+        self.gadgets
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .get(id)
+            .and_then(Weak::upgrade)
+    }
+
+    /// Iterates over the gadgets that are still alive.
+    pub fn live_gadgets(&self) -> impl Iterator<Item = Arc<Gadget<O, G>>> {
+        self.gadgets()
+            .into_iter()
+            .filter_map(|gadget| gadget.upgrade())
+    }
+
+    /// Returns how many entries in the gadget list point to dropped gadgets.
+    pub fn dangling_count(&self) -> usize {
+        self.gadgets
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .dangling_count()
+    }
+
+    /// Removes the entry pointing at `gadget`, called while that gadget is dropped.
+    pub(crate) fn deregister(&self, gadget: &Gadget<O, G>) {
+        // Unlike the `RefCell` version this blocks instead of giving up:
+        // another thread holding the lock will release it shortly,
+        // and this crate never drops a gadget while holding the lock itself.
+        self.write_gadgets().remove(gadget.id(), gadget);
+    }
+
+    /// Removes every dangling entry from the gadget list.
+    ///
+    /// Returns the number of entries removed.
+    pub fn prune(&self) -> usize {
+        self.write_gadgets().prune()
+    }
+
+    pub fn prune_policy(&self) -> PrunePolicy {
+        *self
+            .prune_policy
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Sets the policy used to compact the gadget list on insert.
+    ///
+    /// Kept for parity with the `Rc` owner, but never triggers a prune here:
+    /// a dropped gadget waits for the lock to deregister itself instead of
+    /// leaving a dangling entry behind, so there is nothing to compact.
+    pub fn set_prune_policy(&self, policy: PrunePolicy) {
+        *self
+            .prune_policy
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner) = policy;
+    }
+}
+
+impl<O, G: Default> Owner<O, G> {
+    /// Creates a gadget with a default payload, see [`Owner::add_gadget_with`].
+    pub fn add_gadget(self: &Arc<Self>, id: i32) -> Arc<Gadget<O, G>> {
+        self.add_gadget_with(id, G::default())
+    }
+
+    /// Creates a gadget with a default payload, see [`Owner::try_add_gadget_with`].
+    pub fn try_add_gadget(self: &Arc<Self>, id: i32) -> Result<Arc<Gadget<O, G>>, Error> {
+        self.try_add_gadget_with(id, G::default())
+    }
+
+    /// Creates a gadget with a default payload, see [`Owner::add_next_gadget_with`].
+    pub fn add_next_gadget(self: &Arc<Self>) -> Arc<Gadget<O, G>> {
+        self.add_next_gadget_with(G::default())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeSet;
+    use std::thread;
+
+    use super::*;
+
+    const THREADS: i32 = 8;
+    const PER_THREAD: i32 = 200;
+
+    #[test]
+    fn concurrent_creation_dropping_and_iteration() {
+        let owner = Owner::new("shared");
+
+        let kept: Vec<Arc<Gadget>> = thread::scope(|scope| {
+            let workers: Vec<_> = (0..THREADS)
+                .map(|thread| {
+                    let owner = Arc::clone(&owner);
+                    scope.spawn(move || {
+                        let mut kept = vec![];
+                        for i in 0..PER_THREAD {
+                            let gadget = owner.add_gadget(thread * PER_THREAD + i);
+                            // Odd ids are dropped right away, racing with the other threads.
+                            if i % 2 == 0 {
+                                kept.push(gadget);
+                            }
+                            for live in owner.live_gadgets() {
+                                assert!(Arc::ptr_eq(live.owner(), &owner));
+                            }
+                        }
+                        kept
+                    })
+                })
+                .collect();
+            workers
+                .into_iter()
+                .flat_map(|worker| worker.join().unwrap())
+                .collect()
+        });
+
+        let expected: BTreeSet<i32> = kept.iter().map(|gadget| gadget.id()).collect();
+        let live: BTreeSet<i32> = owner.live_gadgets().map(|gadget| gadget.id()).collect();
+        assert_eq!(live, expected);
+        assert_eq!(live.len(), (THREADS * PER_THREAD / 2) as usize);
+        assert_eq!(owner.gadgets().len(), live.len());
+        assert_eq!(owner.dangling_count(), 0);
+
+        drop(kept);
+        assert_eq!(owner.live_gadgets().count(), 0);
+        assert_eq!(owner.dangling_count(), 0);
+        assert_eq!(Arc::strong_count(&owner), 1);
+    }
+
+    #[test]
+    fn duplicate_ids_are_rejected() {
+        let owner = Owner::new("owner");
+        let gadget = owner.add_gadget(1);
+        assert_eq!(owner.try_add_gadget(1).err(), Some(Error::DuplicateId(1)));
+        assert!(Arc::ptr_eq(&owner.gadget(1).unwrap(), &gadget));
+
+        drop(gadget);
+        assert!(owner.gadget(1).is_none());
+        assert!(owner.try_add_gadget(1).is_ok());
+    }
+
+    #[test]
+    fn concurrent_allocated_ids_are_unique() {
+        let owner = Owner::new("shared");
+        let _taken = owner.add_gadget(2);
+
+        let gadgets: Vec<Arc<Gadget>> = thread::scope(|scope| {
+            let workers: Vec<_> = (0..THREADS)
+                .map(|_| {
+                    let owner = Arc::clone(&owner);
+                    scope.spawn(move || {
+                        (0..PER_THREAD)
+                            .map(|_| owner.add_next_gadget())
+                            .collect::<Vec<_>>()
+                    })
+                })
+                .collect();
+            workers
+                .into_iter()
+                .flat_map(|worker| worker.join().unwrap())
+                .collect()
+        });
+
+        let ids: BTreeSet<i32> = gadgets.iter().map(|gadget| gadget.id()).collect();
+        assert_eq!(ids.len(), gadgets.len());
+        assert!(!ids.contains(&2));
+        for gadget in &gadgets {
+            assert!(Arc::ptr_eq(&owner.gadget(gadget.id()).unwrap(), gadget));
+        }
+    }
+
+    #[test]
+    fn owner_is_send_and_sync() {
+        fn assert_send_sync<T: Send + Sync>() {}
+        assert_send_sync::<Arc<Owner<String, u64>>>();
+        assert_send_sync::<Arc<Gadget<String, u64>>>();
+    }
+}
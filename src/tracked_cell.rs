@@ -0,0 +1,240 @@
+// The `RefCell` behind every interior-mutable field of `Owner` and `Gadget`.
+//
+// A plain `RefCell` only says *that* it is borrowed, not *which* one or *who* holds
+// the borrow, which makes re-entrancy bugs (a callback reaching back into its owner)
+// hard to find. Each cell carries a name reported in `Error::AlreadyBorrowed`.
+// With the `borrow-tracking` feature enabled it also records the caller
+// of every outstanding borrow, and a conflicting borrow reports those locations.
+// Without the feature it is a thin shell around `RefCell`.
+
+use std::cell::{Ref, RefCell, RefMut};
+use std::ops::{Deref, DerefMut};
+use std::panic::Location;
+
+#[cfg(feature = "borrow-tracking")]
+use std::cell::Cell;
+
+use crate::diagnostics::BorrowState;
+use crate::error::Error;
+use crate::owner::fail;
+
+pub(crate) struct TrackedRefCell<T> {
+    name: &'static str,
+    value: RefCell<T>,
+    #[cfg(feature = "borrow-tracking")]
+    holders: RefCell<Vec<(u64, &'static Location<'static>)>>,
+    #[cfg(feature = "borrow-tracking")]
+    next_token: Cell<u64>,
+}
+
+// Removes its entry from `holders` when the borrow ends.
+struct Holder<'a> {
+    #[cfg(feature = "borrow-tracking")]
+    holders: &'a RefCell<Vec<(u64, &'static Location<'static>)>>,
+    #[cfg(feature = "borrow-tracking")]
+    token: u64,
+    #[cfg(not(feature = "borrow-tracking"))]
+    _cell: std::marker::PhantomData<&'a ()>,
+}
+
+impl Drop for Holder<'_> {
+    fn drop(&mut self) {
+        #[cfg(feature = "borrow-tracking")]
+        self.holders
+            .borrow_mut()
+            .retain(|(token, _)| *token != self.token);
+    }
+}
+
+pub(crate) struct TrackedRef<'a, T> {
+    value: Ref<'a, T>,
+    _holder: Holder<'a>,
+}
+
+pub(crate) struct TrackedRefMut<'a, T> {
+    value: RefMut<'a, T>,
+    _holder: Holder<'a>,
+}
+
+impl<T> TrackedRefCell<T> {
+    // `name` says which field this is in error messages, e.g. "gadget list".
+    pub(crate) fn new(name: &'static str, value: T) -> Self {
+        TrackedRefCell {
+            name,
+            value: RefCell::new(value),
+            #[cfg(feature = "borrow-tracking")]
+            holders: RefCell::new(vec![]),
+            #[cfg(feature = "borrow-tracking")]
+            next_token: Cell::new(0),
+        }
+    }
+
+    #[track_caller]
+    pub(crate) fn try_borrow(&self) -> Result<TrackedRef<'_, T>, Error> {
+        match self.value.try_borrow() {
+            Ok(value) => Ok(TrackedRef {
+                value,
+                _holder: self.hold(Location::caller()),
+            }),
+            Err(_) => Err(self.already_borrowed()),
+        }
+    }
+
+    // Like `try_borrow`, for borrows that cannot conflict but should still be tracked.
+    #[track_caller]
+    pub(crate) fn borrow(&self) -> TrackedRef<'_, T> {
+        self.try_borrow().unwrap_or_else(fail)
+    }
+
+    #[track_caller]
+    pub(crate) fn try_borrow_mut(&self) -> Result<TrackedRefMut<'_, T>, Error> {
+        match self.value.try_borrow_mut() {
+            Ok(value) => Ok(TrackedRefMut {
+                value,
+                _holder: self.hold(Location::caller()),
+            }),
+            Err(_) => Err(self.already_borrowed()),
+        }
+    }
+
+    #[track_caller]
+    pub(crate) fn borrow_mut(&self) -> TrackedRefMut<'_, T> {
+        self.try_borrow_mut().unwrap_or_else(fail)
+    }
+
+    fn already_borrowed(&self) -> Error {
+        Error::AlreadyBorrowed {
+            cell: self.name,
+            held_at: self.held_at(),
+        }
+    }
+
+    pub(crate) fn borrow_state(&self) -> BorrowState {
+        BorrowState::of(&self.value)
+    }
+
+    // Where the outstanding borrows were taken; always empty without `borrow-tracking`.
+    pub(crate) fn held_at(&self) -> Vec<&'static Location<'static>> {
+        #[cfg(feature = "borrow-tracking")]
+        return self
+            .holders
+            .borrow()
+            .iter()
+            .map(|(_, location)| *location)
+            .collect();
+        #[cfg(not(feature = "borrow-tracking"))]
+        return vec![];
+    }
+
+    #[cfg(feature = "borrow-tracking")]
+    fn hold(&self, location: &'static Location<'static>) -> Holder<'_> {
+        let token = self.next_token.get();
+        self.next_token.set(token + 1);
+        self.holders.borrow_mut().push((token, location));
+        Holder {
+            holders: &self.holders,
+            token,
+        }
+    }
+
+    #[cfg(not(feature = "borrow-tracking"))]
+    fn hold(&self, _location: &'static Location<'static>) -> Holder<'_> {
+        Holder {
+            _cell: std::marker::PhantomData,
+        }
+    }
+}
+
+impl<T> Deref for TrackedRef<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.value
+    }
+}
+
+impl<T> Deref for TrackedRefMut<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.value
+    }
+}
+
+impl<T> DerefMut for TrackedRefMut<'_, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        &mut self.value
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::owner::Owner;
+
+    #[test]
+    fn conflicts_name_the_cell() {
+        let cell = TrackedRefCell::new("test cell", 0);
+        let _held = cell.try_borrow_mut().unwrap();
+        let err = cell.try_borrow().err().unwrap();
+        assert!(matches!(
+            err,
+            Error::AlreadyBorrowed {
+                cell: "test cell",
+                ..
+            }
+        ));
+        assert!(err.to_string().starts_with("test cell is already borrowed"));
+    }
+
+    #[test]
+    fn owner_operations_report_the_conflicting_cell() {
+        let owner = Owner::new("owner");
+        let _held = owner.gadget_list().try_borrow_mut().unwrap();
+        assert!(matches!(
+            owner.try_add_gadget(1),
+            Err(Error::AlreadyBorrowed {
+                cell: "gadget list",
+                ..
+            })
+        ));
+    }
+
+    #[cfg(feature = "borrow-tracking")]
+    #[test]
+    fn conflicts_report_where_the_borrows_were_taken() {
+        let owner = Owner::new("owner");
+        let first = owner.gadget_list().try_borrow().unwrap();
+        let first_line = line!() - 1;
+        let second = owner.gadget_list().try_borrow().unwrap();
+        let second_line = line!() - 1;
+
+        let Err(Error::AlreadyBorrowed { cell, held_at }) = owner.try_add_gadget(1) else {
+            panic!("the gadget list should be borrowed");
+        };
+        assert_eq!(cell, "gadget list");
+        let lines: Vec<u32> = held_at.iter().map(|location| location.line()).collect();
+        assert_eq!(lines, [first_line, second_line]);
+        assert!(held_at
+            .iter()
+            .all(|location| location.file().ends_with("tracked_cell.rs")));
+
+        // Released borrows are no longer reported.
+        drop(first);
+        let Err(Error::AlreadyBorrowed { held_at, .. }) = owner.try_prune() else {
+            panic!("the gadget list should still be borrowed");
+        };
+        assert_eq!(held_at.len(), 1);
+        assert_eq!(held_at[0].line(), second_line);
+        drop(second);
+        assert!(owner.try_prune().is_ok());
+    }
+
+    #[cfg(not(feature = "borrow-tracking"))]
+    #[test]
+    fn call_sites_are_only_recorded_with_the_feature() {
+        let cell = TrackedRefCell::new("test cell", 0);
+        let _held = cell.try_borrow_mut().unwrap();
+        assert!(cell.held_at().is_empty());
+    }
+}
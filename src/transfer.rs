@@ -0,0 +1,177 @@
+use std::rc::Rc;
+
+use crate::error::Error;
+use crate::gadget::Gadget;
+use crate::owner::{fail, Event, Owner};
+
+/// Moves `gadget` from its primary owner to `new_owner`.
+///
+/// The `Weak` entry is removed from the old owner's gadget list,
+/// registered on the new owner, and the gadget's back-reference is repointed.
+/// Co-owners keep their share; if `new_owner` already is one,
+/// it simply becomes the primary owner.
+/// Every borrow is acquired before anything changes,
+/// so on error both owners and the gadget are left untouched.
+///
+/// Fails with [`Error::DuplicateId`] if `new_owner` already has a live gadget
+/// with the same id, and with [`Error::AlreadyBorrowed`] if any of the
+/// involved `RefCell`s is in use.
+#[track_caller]
+pub fn try_transfer<O, G>(
+    gadget: &Rc<Gadget<O, G>>,
+    new_owner: &Rc<Owner<O, G>>,
+) -> Result<(), Error> {
+    let mut owners = gadget.owners_cell().try_borrow_mut()?;
+    if Rc::ptr_eq(&owners[0], new_owner) {
+        return Ok(());
+    }
+
+    let mut old_gadgets = owners[0].gadget_list().try_borrow_mut()?;
+    let mut new_gadgets = new_owner.gadget_list().try_borrow_mut()?;
+    let co_owner = owners
+        .iter()
+        .position(|candidate| Rc::ptr_eq(candidate, new_owner));
+    if co_owner.is_none()
+        && new_gadgets
+            .get(gadget.id())
+            .is_some_and(|entry| entry.strong_count() > 0)
+    {
+        return Err(Error::DuplicateId(gadget.id()));
+    }
+
+    old_gadgets.remove(gadget.id(), Rc::as_ptr(gadget));
+    if co_owner.is_none() {
+        new_gadgets.push(gadget.id(), Rc::downgrade(gadget));
+    }
+    drop(old_gadgets);
+    drop(new_gadgets);
+
+    let old_owner = match co_owner {
+        Some(position) => {
+            owners.swap(0, position);
+            owners.remove(position)
+        }
+        None => std::mem::replace(&mut owners[0], Rc::clone(new_owner)),
+    };
+    drop(owners);
+
+    let event = Event::GadgetTransferred {
+        id: gadget.id(),
+        from: old_owner.name(),
+        to: new_owner.name(),
+    };
+    old_owner.emit(event.clone());
+    new_owner.emit(event);
+    // The previous owner may be freed right here if this gadget was the last
+    // thing keeping it alive, after every borrow has been released.
+    drop(old_owner);
+    Ok(())
+}
+
+/// Like [`try_transfer`], but panics on failure.
+#[track_caller]
+pub fn transfer<O, G>(gadget: &Rc<Gadget<O, G>>, new_owner: &Rc<Owner<O, G>>) {
+    try_transfer(gadget, new_owner).unwrap_or_else(fail)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // The gadgets listed by `owner`, live or not, as pointers.
+    fn listed(owner: &Owner) -> Vec<*const Gadget> {
+        owner.gadgets().iter().map(|entry| entry.as_ptr()).collect()
+    }
+
+    #[test]
+    fn transfer_moves_the_entry_between_owners() {
+        let old_owner = Owner::new("old");
+        let new_owner = Owner::new("new");
+        let gadget = old_owner.add_gadget(1);
+
+        transfer(&gadget, &new_owner);
+
+        assert!(listed(&old_owner).is_empty());
+        assert!(old_owner.gadget(1).is_none());
+        assert_eq!(listed(&new_owner), [Rc::as_ptr(&gadget)]);
+        assert!(Rc::ptr_eq(&new_owner.gadget(1).unwrap(), &gadget));
+        assert!(Rc::ptr_eq(&gadget.owner(), &new_owner));
+        assert_eq!(gadget.owners().len(), 1);
+    }
+
+    #[test]
+    fn transfer_to_a_co_owner_promotes_it() {
+        let old_owner = Owner::new("old");
+        let new_owner = Owner::new("new");
+        let gadget = old_owner.add_gadget(1);
+        gadget.add_co_owner(&new_owner);
+
+        transfer(&gadget, &new_owner);
+
+        assert!(listed(&old_owner).is_empty());
+        assert!(old_owner.gadget(1).is_none());
+        // Still listed once, not registered a second time.
+        assert_eq!(listed(&new_owner), [Rc::as_ptr(&gadget)]);
+        assert!(Rc::ptr_eq(&new_owner.gadget(1).unwrap(), &gadget));
+        assert_eq!(gadget.owners().len(), 1);
+        assert!(Rc::ptr_eq(&gadget.owner(), &new_owner));
+    }
+
+    #[test]
+    fn transfer_keeps_other_co_owners() {
+        let old_owner = Owner::new("old");
+        let co_owner = Owner::new("co");
+        let new_owner = Owner::new("new");
+        let gadget = old_owner.add_gadget(1);
+        gadget.add_co_owner(&co_owner);
+
+        transfer(&gadget, &new_owner);
+
+        assert!(listed(&old_owner).is_empty());
+        assert_eq!(listed(&co_owner), [Rc::as_ptr(&gadget)]);
+        assert_eq!(listed(&new_owner), [Rc::as_ptr(&gadget)]);
+        assert!(Rc::ptr_eq(&gadget.owner(), &new_owner));
+        assert!(gadget.is_owned_by(&co_owner));
+    }
+
+    #[test]
+    fn failed_transfer_changes_nothing() {
+        let old_owner = Owner::new("old");
+        let new_owner = Owner::new("new");
+        let gadget = old_owner.add_gadget(1);
+        let taken = new_owner.add_gadget(1);
+
+        assert_eq!(
+            try_transfer(&gadget, &new_owner),
+            Err(Error::DuplicateId(1))
+        );
+
+        assert_eq!(listed(&old_owner), [Rc::as_ptr(&gadget)]);
+        assert!(Rc::ptr_eq(&old_owner.gadget(1).unwrap(), &gadget));
+        assert_eq!(listed(&new_owner), [Rc::as_ptr(&taken)]);
+        assert!(Rc::ptr_eq(&new_owner.gadget(1).unwrap(), &taken));
+        assert!(Rc::ptr_eq(&gadget.owner(), &old_owner));
+        assert_eq!(gadget.owners().len(), 1);
+    }
+
+    #[test]
+    fn transfer_back_and_forth_keeps_lists_consistent() {
+        let first = Owner::new("first");
+        let second = Owner::new("second");
+        let gadgets: Vec<_> = (1..=3).map(|id| first.add_gadget(id)).collect();
+
+        transfer(&gadgets[1], &second);
+        transfer(&gadgets[1], &first);
+        transfer(&gadgets[2], &second);
+
+        let ids = |owner: &Owner| {
+            owner
+                .live_gadgets()
+                .map(|gadget| gadget.id())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(ids(&first), [1, 2]);
+        assert_eq!(ids(&second), [3]);
+        assert_eq!(first.dangling_count() + second.dangling_count(), 0);
+    }
+}